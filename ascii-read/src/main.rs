@@ -1,22 +1,37 @@
-use std::{fs::File, io::{stdout, Read, Write}};
+use std::{env, fs, io::stdout, process};
 
+use ascii_gen::{output, Canvas, ColorDepth, ColorMode, LoadError, LoadOptions, OutputFormat};
 use termion::raw::IntoRawMode;
 
 fn main() {
-  let path = "../kirby.out";
+  let path = env::args().nth(1).unwrap_or("../kirby.out".to_string());
 
-  let mut file = File::open(path).expect("No file found");
+  let bytes = match fs::read(&path) {
+    Ok(bytes) => bytes,
+    Err(err) => {
+      eprintln!("{}: {}", path, err);
+      process::exit(1);
+    }
+  };
 
-  let mut content = String::new();
-  file.read_to_string(&mut content).unwrap();
+  let options = LoadOptions {
+    color_depth: ColorDepth::detect(),
+    ..LoadOptions::new(96, 48)
+  };
+  // Images are converted on the fly, anything else is replayed as saved output.
+  let content = match Canvas::from_bytes(&bytes, &options) {
+    Ok(canvas) => {
+      let mut ansi = vec![];
+      canvas.write_to(&mut ansi, OutputFormat::Ansi, ColorMode::Full).unwrap();
+      String::from_utf8_lossy(&ansi).into_owned()
+    }
+    Err(LoadError::UnsupportedFormat(_)) => String::from_utf8_lossy(&bytes).into_owned(),
+    Err(err) => {
+      eprintln!("{}: {}", path, err);
+      process::exit(1);
+    }
+  };
 
   let mut handle = stdout().into_raw_mode().unwrap().lock();
-
-  for char in content.chars() {
-    if char == '\n' {
-      write!(handle, "\r\n").unwrap();
-    } else {
-      write!(handle, "{}", char).unwrap();
-    }
-  }
+  output::write_raw(&content, &mut handle).unwrap();
 }
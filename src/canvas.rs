@@ -0,0 +1,317 @@
+//! The canvas and the live, diff-based terminal display.
+
+use std::{
+    io::{stdout, BufWriter, Write},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use termion::cursor;
+
+use crate::{
+    Cell, Circle, Color, ColorDepth, ColorMetric, ColorSpace, DialogBox, Rectangle, Vector2,
+};
+
+/// Something drawn over the canvas for a single [`Canvas::display`] call.
+#[derive(Clone)]
+pub enum Renderable {
+    /// A bordered box of text.
+    DialogBox(DialogBox),
+    /// A rectangle filled with a cell, blended at the given alpha.
+    Rectangle(Rectangle, Cell, f32),
+    /// A circle filled with a cell.
+    Circle(Circle, Cell),
+}
+
+impl Renderable {
+    /// Returns a copy of `canvas` with this drawn over it.
+    pub fn render(&self, canvas: &mut Canvas) -> Canvas {
+        match self {
+            Renderable::DialogBox(dialog) => canvas.draw_dialog_box(dialog.clone()),
+            Renderable::Rectangle(rect, cell, alpha) => canvas.draw_rectangle(rect.clone(), *cell, *alpha),
+            Renderable::Circle(circle, cell) => canvas.draw_circle(circle.clone(), *cell),
+        }
+    }
+}
+
+/// A grid of cells plus the overlays to draw on top of it.
+#[derive(Clone)]
+pub struct Canvas {
+    /// The cells, row by row.
+    pub cells: Vec<Vec<Cell>>,
+    /// Overlays drawn by the next [`Canvas::display`] and then cleared.
+    pub renderables: Vec<Renderable>,
+    old_canvas: Option<Box<Canvas>>,
+    /// The space colours are blended in.
+    pub color_space: ColorSpace,
+    /// Which escape sequences the cells are drawn and written with.
+    pub color_depth: ColorDepth,
+    /// How colours are compared, both against the terminal's palette and to
+    /// skip escape codes for cells close to the previous one.
+    pub color_metric: ColorMetric,
+}
+
+impl Canvas {
+    /// Number of rows.
+    pub fn height(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Number of columns, or 0 for an empty canvas.
+    pub fn width(&self) -> usize {
+        self.cells.first().map(|v| v.len()).unwrap_or(0)
+    }
+
+    /// Draws the canvas and its pending renderables at the top of the terminal,
+    /// rewriting only the cells that changed since the previous call.
+    pub fn display(&mut self) {
+        let mut handle = BufWriter::new(stdout());
+        write!(handle, "\x1b[H").unwrap();
+
+        let mut fg = Color::black();
+        let mut bg = Color::black();
+        let mut first = true;
+
+        let mut canvas = self.clone();
+
+        // Cells from a canvas of another size are at stale coordinates.
+        if self.old_canvas.as_ref().is_some_and(|old| {
+            old.width() != self.width() || old.height() != self.height()
+        }) {
+            self.invalidate();
+        }
+        if self.old_canvas.is_none() {
+            write!(handle, "\x1b[0m\x1b[2J").unwrap();
+        }
+
+        for renderable in self.renderables.clone().into_iter() {
+            canvas = renderable.render(&mut canvas);
+        }
+
+        let metric = self.color_metric;
+        let tolerance = metric.tolerance();
+
+        for y in 0..canvas.height() {
+            for x in 0..canvas.width() {
+                let cell = canvas.cells[y][x];
+                let should_write = match &self.old_canvas {
+                    None => true,
+                    Some(old) => cell != old.cells[y][x],
+                };
+                if should_write {
+                    write!(handle, "{}", cursor::Goto(x as u16 + 1, y as u16 + 1)).unwrap();
+                    if !first
+                        && metric.distance(cell.fg, fg) < tolerance
+                        && metric.distance(cell.bg, bg) < tolerance
+                    {
+                        write!(handle, "{}", cell.char).unwrap();
+                    } else {
+                        write!(handle, "\x1b[0m").unwrap();
+                        write!(handle, "{}", cell.render_with(self.color_depth, metric)).unwrap();
+                        fg = cell.fg;
+                        bg = cell.bg;
+                        first = false;
+                    }
+                    //let _ = handle.flush();
+                }
+                match &mut self.old_canvas {
+                    None => {},
+                    Some(old) => old.cells[y][x] = cell,
+                }
+            }
+            first = true;
+            writeln!(handle, "\x1b[0m").unwrap();
+        }
+        
+        if self.old_canvas.is_none() {
+            self.old_canvas = Some(Box::new(Canvas::from_cells(canvas.cells, self.color_space)));
+        }
+        self.renderables.clear();
+        sleep(Duration::from_millis(66 / 4));
+    }
+
+    /// Forgets what is on screen so the next [`Canvas::display`] clears the
+    /// terminal and repaints every cell.
+    pub fn invalidate(&mut self) {
+        self.old_canvas = None;
+    }
+
+    /// Shows each frame for its delay, redrawing only the cells that changed.
+    /// Loops forever when `loops` is `None`.
+    pub fn play(frames: &[(Canvas, Duration)], loops: Option<usize>) {
+        let Some((first, _)) = frames.first() else {
+            return;
+        };
+        let mut screen = first.clone();
+        let mut played = 0;
+
+        while loops.is_none_or(|loops| played < loops) {
+            for (frame, delay) in frames {
+                let start = Instant::now();
+                screen.cells = frame.cells.clone();
+                screen.display();
+                sleep(delay.saturating_sub(start.elapsed()));
+            }
+            played += 1;
+        }
+    }
+
+    /// A canvas filled with black cells.
+    pub fn new(width: usize, height: usize) -> Self {
+        let cell = Cell::from_color(Color::new(0, 0, 0));
+        Self {
+            cells: vec![vec![cell; width]; height],
+            renderables: vec![],
+            old_canvas: None,
+            color_space: ColorSpace::Srgb,
+            color_depth: ColorDepth::TrueColor,
+            color_metric: ColorMetric::Euclidean,
+        }
+    }
+
+    /// Wraps already converted cells, e.g. from an image loader.
+    pub fn from_cells(cells: Vec<Vec<Cell>>, color_space: ColorSpace) -> Self {
+        Self {
+            cells,
+            renderables: vec![],
+            old_canvas: None,
+            color_space,
+            color_depth: ColorDepth::TrueColor,
+            color_metric: ColorMetric::Euclidean,
+        }
+    }
+
+    /// Replaces the cell at `x`, `y`, ignoring positions outside the canvas.
+    pub fn set(&mut self, x: usize, y: usize, cell: Cell) {
+        if let Some(target) = self.cells.get_mut(y).and_then(|row| row.get_mut(x)) {
+            *target = cell;
+        }
+    }
+
+    /// Returns a copy with `rect` filled by `cell`, blended at `alpha`.
+    pub fn draw_rectangle(&mut self, rect: Rectangle, cell: Cell, alpha: f32) -> Canvas {
+        let mut canvas = self.clone();
+        
+        for y in rect.position.y as usize..(rect.position.y + rect.size.y) as usize {
+            if y >= self.height() {
+                break;
+            }
+            for x in rect.position.x as usize..(rect.position.x + rect.size.x) as usize {
+                if x >= self.width() {
+                    break;
+                }
+                let orig = self.cells[y][x];
+                let (fg1, bg1) = (orig.fg, orig.bg);
+                let (fg2, bg2) = (cell.fg, cell.bg);
+                let fg = fg1.blend(fg2, alpha, self.color_space);
+                let bg = bg1.blend(bg2, alpha, self.color_space);
+                canvas.cells[y][x] = Cell::new(cell.char, fg, bg);
+            }
+        }
+
+        canvas
+    }
+
+    /// Returns a copy with `circle` filled by `cell`.
+    pub fn draw_circle(&mut self, circle: Circle, cell: Cell) -> Canvas {
+        let mut canvas = self.clone();
+        let center = Vector2::new(circle.x / 2.0, circle.y);
+
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let p = Vector2::new(x as f32 / 2.0, y as f32);
+                if p.distance(center) <= circle.radius {
+                    canvas.cells[y][x] = cell;
+                }
+            }
+        }
+
+        canvas
+    }
+
+    /// Returns a copy with `dialog` drawn centred on it.
+    pub fn draw_dialog_box(&mut self, dialog: DialogBox) -> Canvas {
+        let width = dialog.width + dialog.x_pad;
+        let height = dialog.height + dialog.y_pad;
+
+        let mut canvas = self.clone();
+
+        let x = canvas.width() as f32 / 2.0 - dialog.width / 2.0;
+        let mut xblack = Cell::from_color(Color::black());
+        let mut yblack = Cell::from_color(Color::black());
+        xblack.char = '─';
+        xblack.fg = Color::white();
+        yblack.char = '│';
+        yblack.fg = Color::white();
+        let base = Rectangle::new(
+            Vector2::new(x, dialog.position),
+            Vector2::new(width, height),
+        );
+
+        canvas = canvas.draw_rectangle(base, Cell::from_color(Color::black()), 0.7);
+
+        let mut corner = Cell::new(' ', Color::white(), Color::black());
+
+        let x = x - 1.0;
+
+        corner.char = '╭';
+        canvas.set(x as usize, (dialog.position - 1.0) as usize, corner);
+        corner.char = '╰';
+        canvas.set(x as usize, (dialog.position + height) as usize, corner);
+
+        let left = Rectangle::new(
+            Vector2::new(x, dialog.position),
+            Vector2::new(1.0, height),
+        );
+
+        canvas = canvas.draw_rectangle(left, yblack, 1.0);
+
+        let x = canvas.width() as f32 / 2.0 + width / 2.0 + 1.0;
+
+        corner.char = '╮';
+        canvas.set(x as usize, (dialog.position - 1.0) as usize, corner);
+        corner.char = '╯';
+        canvas.set(x as usize, (dialog.position + height) as usize, corner);
+
+        let right = Rectangle::new(
+            Vector2::new(x, dialog.position),
+            Vector2::new(1.0, height),
+        );
+        
+        canvas = canvas.draw_rectangle(right, yblack, 1.0);
+
+        let x = canvas.width() as f32 / 2.0 - dialog.width / 2.0;
+        let y = dialog.position - 1.0;
+        let top = Rectangle::new(Vector2::new(x, y), Vector2::new(width, 1.0));
+
+        canvas = canvas.draw_rectangle(top, xblack, 0.9);
+
+        let y = dialog.position + height;
+        let bottom = Rectangle::new(Vector2::new(x, y), Vector2::new(width, 1.0));
+
+        canvas = canvas.draw_rectangle(bottom, xblack, 0.9);
+
+        let mut y = dialog.position + dialog.y_pad;
+        let mut x = canvas.width() as f32 / 2.0 - dialog.width / 2.0 + dialog.x_pad;
+        let chars = dialog.text.chars();
+        for char in chars {
+            if char == '\n' {
+                y += 1.0;
+                x = canvas.width() as f32 / 2.0 - dialog.width / 2.0 + dialog.x_pad;
+            } else {
+                x += 1.0;
+            }
+            if y as usize >= canvas.height() {
+                continue;
+            }
+            if x as usize >= canvas.width() {
+                continue;
+            }
+            canvas.cells[y as usize][x as usize].char = char;
+            canvas.cells[y as usize][x as usize].fg = dialog.text_color;
+        }
+
+        canvas
+    }
+}
+
@@ -0,0 +1,425 @@
+//! Terminal cells and the mapping from colours to glyphs.
+
+use crate::{
+    glyphs::{SHAPE_HEIGHT, SHAPE_WIDTH},
+    Color, ColorDepth, ColorMetric, ColorSpace, GlyphSet, Palette,
+};
+
+//const DEFAULT_RAMP: &str = ".,:;!•ag?$&@";
+/// Glyphs ordered from the darkest to the brightest.
+pub const DEFAULT_RAMP: &str = "`'~!,-\":|\\;/(<>)]+[{}i731t2sy*ur5o=dea49p6q&8w€¥0$%@#";
+
+// Brightness spread below which a Braille cell counts as a flat area.
+const BRAILLE_MIN_CONTRAST: f64 = 32.0;
+// Gradient, in brightness steps per pixel, from which a cell gets an edge glyph.
+const EDGE_MIN_STRENGTH: f64 = 24.0;
+
+/// One character on the terminal, with its foreground and background colours.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cell {
+    /// The character drawn.
+    pub char: char,
+    /// Foreground colour, used for the character itself.
+    pub fg: Color,
+    /// Background colour, filling the rest of the cell.
+    pub bg: Color,
+}
+
+impl Cell {
+    /// Creates a cell from its parts.
+    pub fn new(char: char, fg: Color, bg: Color) -> Self {
+        Self { char, fg, bg }
+    }
+
+    /// Picks a glyph and colours approximating `color` from [`DEFAULT_RAMP`].
+    pub fn from_color(color: Color) -> Self {
+        let ramp = DEFAULT_RAMP.chars().collect::<Vec<_>>();
+        Cell::from_color_with_ramp(color, &ramp)
+    }
+
+    /// Like [`Cell::from_color`], with both colours mapped to their nearest
+    /// colours in `palette`.
+    pub fn from_color_in_palette(color: Color, palette: &Palette, metric: ColorMetric) -> Self {
+        palette.snap(Cell::from_color(color), metric)
+    }
+
+    /// Like [`Cell::from_color`] with a custom ramp, which runs from the darkest
+    /// glyph to the brightest. An empty ramp falls back to [`DEFAULT_RAMP`].
+    pub fn from_color_with_ramp(color: Color, ramp: &[char]) -> Self {
+        Cell::from_color_dithered(color, ramp, 0.5)
+    }
+
+    /// Like [`Cell::from_color_with_ramp`], rounding the glyph up or down the
+    /// ramp by an ordered-dither `threshold` (0.0 to 1.0, 0.5 for neither).
+    pub fn from_color_dithered(color: Color, ramp: &[char], threshold: f32) -> Self {
+        if ramp.is_empty() {
+            let ramp = DEFAULT_RAMP.chars().collect::<Vec<_>>();
+            return Cell::from_color_dithered(color, &ramp, threshold);
+        }
+        let count = ramp.len();
+        let (c1, c2, w) = color.decompose();
+        let w = ((w * (count - 1) as f32) + threshold - 1.0) as usize;
+        let w = w.clamp(0, count - 1);
+        Cell::new(ramp[w], c1, c2)
+    }
+
+    /// Two vertically stacked pixels in one cell: `▀` drawn in the top colour
+    /// over the bottom colour.
+    pub fn half_block(top: Color, bottom: Color) -> Self {
+        Cell::new('▀', top, bottom)
+    }
+
+    /// Eight pixels, two across and four down in row-major order, as a
+    /// Braille pattern: the dots mark the brighter pixels and are drawn in
+    /// their average colour over the average of the others, taken in `space`.
+    pub fn braille(pixels: &[Color], space: ColorSpace) -> Self {
+        // Bit of each dot in the U+2800 block, in the same order as `pixels`.
+        const DOTS: [u32; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+
+        let brightness = pixels.iter().map(Color::brightness).collect::<Vec<_>>();
+        let (min, max) = brightness
+            .iter()
+            .fold((f64::MAX, f64::MIN), |(min, max), &b| (min.min(b), max.max(b)));
+        // Split at the cell's own midpoint so thin lines survive on any
+        // background; nearly flat cells are all dots or none by a fixed threshold.
+        let threshold = if max - min < BRAILLE_MIN_CONTRAST {
+            127.5
+        } else {
+            (min + max) / 2.0
+        };
+
+        let mut bits = 0;
+        let (mut on, mut off) = (vec![], vec![]);
+        for (i, (&color, &b)) in pixels.iter().zip(brightness.iter()).enumerate() {
+            if b > threshold {
+                bits |= DOTS[i];
+                on.push(color);
+            } else {
+                off.push(color);
+            }
+        }
+
+        let all = Color::average(pixels, space);
+        let fg = if on.is_empty() { all } else { Color::average(&on, space) };
+        let bg = if off.is_empty() { all } else { Color::average(&off, space) };
+        Cell::new(char::from_u32(0x2800 + bits).unwrap_or(' '), fg, bg)
+    }
+
+    /// Four pixels, two across and two down in row-major order, as the
+    /// quadrant block and pair of colours that reproduce them best in `space`.
+    pub fn quadrant(pixels: &[Color], space: ColorSpace) -> Self {
+        const GLYPHS: [char; 16] = [
+            ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+        ];
+        let (mask, fg, bg) = fit_two_colors(pixels, 0..16, space);
+        Cell::new(GLYPHS[mask as usize], fg, bg)
+    }
+
+    /// A `width`-wide block of pixels in row-major order as a line glyph
+    /// (`|`, `-`, `_`, `/` or `\\`) following the edge running through it, or
+    /// a ramp glyph (see [`Cell::from_color_dithered`]) where there is no
+    /// clear edge. `gradients` are the block's pixels' [`sobel`] gradients,
+    /// taken over the whole image so edges on the cell's border count. The
+    /// glyph is drawn in the block's average colour in `space`.
+    pub fn edge(
+        pixels: &[Color],
+        gradients: &[[f64; 2]],
+        width: usize,
+        ramp: &[char],
+        threshold: f32,
+        space: ColorSpace,
+    ) -> Self {
+        let average = Color::average(pixels, space);
+        let height = pixels.len() / width.max(1);
+        let Some((angle, row)) = edge_direction(gradients, width) else {
+            return Cell::from_color_dithered(average, ramp, threshold);
+        };
+
+        // `angle` runs anticlockwise from the horizontal, in degrees.
+        let glyph = match angle {
+            a if (22.5..67.5).contains(&a) => '/',
+            a if (67.5..112.5).contains(&a) => '|',
+            a if (112.5..157.5).contains(&a) => '\\',
+            // A horizontal edge along the bottom of the cell.
+            _ if row * 3 >= height * 2 => '_',
+            _ => '-',
+        };
+        let (fg, bg, _) = average.decompose();
+        Cell::new(glyph, fg, bg)
+    }
+
+    /// Six pixels, two across and three down in row-major order, as the
+    /// sextant block (Symbols for Legacy Computing) and pair of colours that
+    /// reproduce them best in `space`.
+    pub fn sextant(pixels: &[Color], space: ColorSpace) -> Self {
+        let (mask, fg, bg) = fit_two_colors(pixels, 0..64, space);
+        let glyph = match mask {
+            0 => ' ',
+            21 => '▌',
+            42 => '▐',
+            63 => '█',
+            // The block skips the patterns above, which exist elsewhere.
+            _ => {
+                let skipped = (mask > 21) as u32 + (mask > 42) as u32;
+                char::from_u32(0x1FB00 + mask as u32 - 1 - skipped).unwrap_or(' ')
+            }
+        };
+        Cell::new(glyph, fg, bg)
+    }
+
+    /// The truecolor escape sequences followed by the glyph.
+    pub fn render(&self) -> String {
+        self.render_with(ColorDepth::TrueColor, ColorMetric::Euclidean)
+    }
+
+    /// Like [`Cell::render`], with the colours the terminal can show closest
+    /// to the cell's by `metric`.
+    pub fn render_with(&self, depth: ColorDepth, metric: ColorMetric) -> String {
+        format!("{}{}{}", depth.fg(self.fg, metric), depth.bg(self.bg, metric), self.char)
+    }
+}
+
+// Picks the mask (bit `i` set when pixel `i` is drawn in the foreground) and
+// colours that minimise the squared error over `pixels` in `space`. For a given
+// mask the best colours are simply the means of each side, so only the masks
+// are searched.
+fn fit_two_colors(
+    pixels: &[Color],
+    masks: impl Iterator<Item = u64>,
+    space: ColorSpace,
+) -> (u64, Color, Color) {
+    let average = Color::average(pixels, space);
+    let mut best = (0, average, average);
+    let mut best_error = f32::MAX;
+
+    for mask in masks {
+        let mut sums = [[0.0f32; 3]; 2];
+        let mut counts = [0.0f32; 2];
+        let mut squares = 0.0;
+        for (i, pixel) in pixels.iter().enumerate() {
+            let side = (mask >> i & 1) as usize;
+            let c = pixel.channels(space);
+            for k in 0..3 {
+                sums[side][k] += c[k];
+                squares += c[k] * c[k];
+            }
+            counts[side] += 1.0;
+        }
+        // Sum of squared deviations from each side's mean: Σx² - (Σx)²/n.
+        let mut error = squares;
+        for side in 0..2 {
+            if counts[side] > 0.0 {
+                error -= sums[side].iter().map(|s| s * s).sum::<f32>() / counts[side];
+            }
+        }
+
+        if error < best_error - 1e-3 {
+            best_error = error;
+            let mean = |side: usize| {
+                if counts[side] == 0.0 {
+                    return average;
+                }
+                Color::from_channels(sums[side].map(|s| s / counts[side]), space)
+            };
+            best = (mask, mean(1), mean(0));
+        }
+    }
+
+    // Keep the brighter side in the foreground, so the glyphs still read
+    // right when the colours are dropped.
+    let (mask, fg, bg) = best;
+    if fg.brightness() < bg.brightness() {
+        let full = (1u64 << pixels.len()) - 1;
+        return (!mask & full, bg, fg);
+    }
+    best
+}
+
+/// The Sobel gradients (across, down) of the brightness of every pixel in a
+/// `width`-wide grid, repeating the outermost pixels past its edges.
+pub fn sobel(pixels: &[Color], width: usize) -> Vec<[f64; 2]> {
+    if width == 0 {
+        return vec![];
+    }
+    let height = pixels.len() / width;
+    let at = |x: isize, y: isize| {
+        let x = x.clamp(0, width as isize - 1) as usize;
+        let y = y.clamp(0, height as isize - 1) as usize;
+        pixels[y * width + x].brightness()
+    };
+
+    (0..width * height)
+        .map(|i| {
+            let (x, y) = ((i % width) as isize, (i / width) as isize);
+            let gx = (at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x - 1, y)
+                - at(x - 1, y + 1))
+                / 4.0;
+            let gy = (at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x, y - 1)
+                - at(x + 1, y - 1))
+                / 4.0;
+            [gx, gy]
+        })
+        .collect()
+}
+
+// Finds the dominant edge in a `width`-wide block of Sobel gradients. Their
+// structure tensor, unlike a plain average, doesn't cancel out across a thin
+// line whose two sides slope in opposite directions. Returns the edge's angle
+// (0 to 180 degrees) and the row where it is strongest, or `None` for smooth
+// blocks.
+fn edge_direction(gradients: &[[f64; 2]], width: usize) -> Option<(f64, usize)> {
+    if gradients.is_empty() || width == 0 {
+        return None;
+    }
+    let (mut xx, mut yy, mut xy) = (0.0, 0.0, 0.0);
+    let mut strongest = (0.0, 0);
+
+    for (i, &[gx, gy]) in gradients.iter().enumerate() {
+        xx += gx * gx;
+        yy += gy * gy;
+        xy += gx * gy;
+        if gy.abs() > strongest.0 {
+            strongest = (gy.abs(), i / width);
+        }
+    }
+    let count = gradients.len() as f64;
+    let (xx, yy, xy) = (xx / count, yy / count, xy / count);
+
+    // The larger eigenvalue is the squared gradient across the edge.
+    let largest = (xx + yy) / 2.0 + (((xx - yy) / 2.0).powi(2) + xy * xy).sqrt();
+    if largest.sqrt() < EDGE_MIN_STRENGTH {
+        return None;
+    }
+
+    // The gradient's angle with y pointing down, turned a right angle to run
+    // along the edge and flipped to y pointing up.
+    let gradient = 0.5 * (2.0 * xy).atan2(xx - yy);
+    let angle = (90.0 - gradient.to_degrees()).rem_euclid(180.0);
+    Some((angle, strongest.1))
+}
+
+/// How the pixels under a cell are turned into its glyph and colours.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CellMode {
+    /// One pixel per cell, drawn with a glyph from the luminance ramp.
+    Ramp,
+    /// Two stacked pixels per cell, see [`Cell::half_block`].
+    HalfBlock,
+    /// A 2x4 grid of dots per cell, see [`Cell::braille`].
+    Braille,
+    /// 2x2 pixels per cell, see [`Cell::quadrant`].
+    Quadrant,
+    /// 2x3 pixels per cell, see [`Cell::sextant`].
+    Sextant,
+    /// Line glyphs along edges and the ramp elsewhere, see [`Cell::edge`].
+    Edge,
+    /// Whole glyphs matched against a block of pixels, see [`GlyphSet::cell`].
+    Shape,
+}
+
+impl CellMode {
+    /// How many pixels across and down each cell stands for.
+    pub fn subpixels(&self) -> (usize, usize) {
+        match self {
+            CellMode::Ramp => (1, 1),
+            CellMode::HalfBlock => (1, 2),
+            CellMode::Braille => (2, 4),
+            CellMode::Quadrant => (2, 2),
+            CellMode::Sextant => (2, 3),
+            // Square pixels in a 1:2 cell.
+            CellMode::Edge => (3, 6),
+            CellMode::Shape => (SHAPE_WIDTH, SHAPE_HEIGHT),
+        }
+    }
+
+    /// The nearest colour the mode can show to `color`: ramp glyphs snap their
+    /// colours to steps of 16 (see [`Color::decompose`]), other modes show it
+    /// as is.
+    pub fn quantise(&self, color: Color) -> Color {
+        match self {
+            CellMode::Ramp | CellMode::Edge => color.clamp(0, 255, 8).blockify(16),
+            _ => color,
+        }
+    }
+
+    /// How far apart the levels [`CellMode::quantise`] snaps to are, for
+    /// ordered dithering; 0.0 when it keeps colours as they are.
+    pub fn step(&self) -> f32 {
+        match self {
+            CellMode::Ramp | CellMode::Edge => 16.0,
+            _ => 0.0,
+        }
+    }
+
+    /// Maps the pixels under one cell, in row-major order, to that cell.
+    /// `threshold` dithers the choice of ramp glyphs, see
+    /// [`Cell::from_color_dithered`], and pixels are averaged in `space`.
+    /// Edges are only looked for within the block; [`crate::image_to_canvas`]
+    /// also finds them on cell borders.
+    pub fn cell(&self, pixels: &[Color], ramp: &[char], threshold: f32, space: ColorSpace) -> Cell {
+        match self {
+            CellMode::Ramp => Cell::from_color_dithered(pixels[0], ramp, threshold),
+            CellMode::HalfBlock => Cell::half_block(pixels[0], pixels[1]),
+            CellMode::Braille => Cell::braille(pixels, space),
+            CellMode::Quadrant => Cell::quadrant(pixels, space),
+            CellMode::Sextant => Cell::sextant(pixels, space),
+            CellMode::Edge => Cell::edge(pixels, &sobel(pixels, 3), 3, ramp, threshold, space),
+            CellMode::Shape => GlyphSet::builtin().cell(pixels, space),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The `x`th 3x6 block of a 12-wide grid.
+    fn block<T: Copy>(grid: &[T], x: usize) -> Vec<T> {
+        (0..6).flat_map(|y| grid[y * 12 + x * 3..][..3].to_vec()).collect()
+    }
+
+    #[test]
+    fn sextant_code_points() {
+        // White where the mask has a bit set, pixel 0 being bit 0.
+        let glyph = |mask: u32| {
+            let pixels = (0..6)
+                .map(|i| if mask >> i & 1 == 1 { Color::new(255, 255, 255) } else { Color::new(0, 0, 0) })
+                .collect::<Vec<_>>();
+            Cell::sextant(&pixels, ColorSpace::Srgb).char
+        };
+        assert_eq!(glyph(1), '\u{1FB00}');
+        assert_eq!(glyph(20), '\u{1FB13}');
+        // The two half blocks are left out of the sextant range.
+        assert_eq!(glyph(21), '▌');
+        assert_eq!(glyph(22), '\u{1FB14}');
+        assert_eq!(glyph(41), '\u{1FB27}');
+        assert_eq!(glyph(42), '▐');
+        assert_eq!(glyph(43), '\u{1FB28}');
+        assert_eq!(glyph(62), '\u{1FB3B}');
+    }
+
+    #[test]
+    fn empty_ramps_fall_back_to_the_default() {
+        let color = Color::new(200, 200, 200);
+        let ramp = DEFAULT_RAMP.chars().collect::<Vec<_>>();
+        assert_eq!(Cell::from_color_with_ramp(color, &[]), Cell::from_color_with_ramp(color, &ramp));
+    }
+
+    #[test]
+    fn edges_on_cell_borders_are_found() {
+        // A step between x = 5 and x = 6, the border of the 2nd and 3rd cells.
+        let pixels = (0..72)
+            .map(|i| if i % 12 < 6 { Color::new(0, 0, 0) } else { Color::new(255, 255, 255) })
+            .collect::<Vec<_>>();
+        let gradients = sobel(&pixels, 12);
+        let ramp = DEFAULT_RAMP.chars().collect::<Vec<_>>();
+        let glyphs = (0..4)
+            .map(|x| Cell::edge(&block(&pixels, x), &block(&gradients, x), 3, &ramp, 0.5, ColorSpace::Srgb).char)
+            .collect::<String>();
+        assert_eq!(&glyphs[1..3], "||");
+    }
+}
@@ -0,0 +1,368 @@
+use ascii_gen::{
+    Anchor, CellMode, Color, ColorDepth, ColorMetric, ColorMode, ColorSpace, Filter, FitMode, Kernel,
+    OutputFormat, Palette, ThresholdMap,
+};
+
+pub const USAGE: &str = "\
+Usage: ascii-gen [OPTIONS] <INPUT>...
+       ascii-gen demo [IMAGE]
+       ascii-gen ramp <FONT> [CHARS]
+       ascii-gen palette <IMAGE> [COLOURS]
+
+Converts images (JPEG, PNG, GIF, BMP, PNM) into coloured text.
+Use - as an input to read the image from stdin.
+
+`ramp` prints CHARS (by default the built-in ramp) sorted by how much ink
+each glyph has in a BDF or PSF font, ready to pass to --ramp.
+
+`palette` prints COLOURS (by default 16) colours picked from IMAGE as a hex
+list, ready to save and pass to --palette.
+
+Options:
+  -w, --width <N>        Canvas width in cells [default: 96]
+  -H, --height <N>       Canvas height in cells [default: 48]
+  -t, --fit-terminal     Size the canvas to the terminal window
+      --fit <MODE>       stretch, contain, cover or shrink
+                         [default: stretch, or shrink with --fit-terminal]
+  -f, --format <FMT>     ansi, text or html [default: ansi]
+  -c, --color <MODE>     full, fg or none [default: full]
+      --colors <DEPTH>   truecolor, 256, 16 or auto (from COLORTERM and
+                         TERM) [default: auto]
+      --metric <NAME>    How colours are compared: euclidean, redmean,
+                         cie76 or ciede2000 [default: euclidean]
+  -p, --palette <PAL>    Limit the colours to pico-8, game-boy, cga, a GIMP,
+                         Paint.NET or hex list palette file, or IMAGE:N for
+                         N colours picked from an image
+  -m, --mode <MODE>      ramp, half (1x2 pixels per cell), quadrant (2x2),
+                         sextant (2x3), braille (2x4 dots), edge (line
+                         glyphs along outlines) or shape (glyphs matched to
+                         4x8 pixel blocks) [default: ramp]
+  -r, --ramp <CHARS>     Glyphs to use, from darkest to brightest
+      --ramp-font <FONT> Sort the ramp by ink coverage in a BDF or PSF font
+      --shape-font <FONT>
+                         Match shapes against a BDF or PSF font's glyphs (the
+                         --ramp glyphs, or printable ASCII)
+  -d, --dither <KIND>    none, floyd-steinberg, atkinson, sierra, bayer[:SIZE]
+                         (SIZE a power of two up to 64) or
+                         blue-noise:<IMAGE> (a threshold texture)
+                         [default: none]
+      --no-serpentine    Diffuse dithering errors left to right on every row
+      --filter <NAME>    box, bilinear or lanczos [default: box]
+      --linear           Average and blend colours in linear light
+  -a, --animate          Play animated GIFs instead of printing a frame
+  -o, --output <FILE>    Write to a file instead of stdout
+  -h, --help             Print this help
+";
+
+#[derive(Clone, Debug)]
+pub enum Command {
+    Convert(Args),
+    Demo(String),
+    Ramp(String, Option<String>),
+    Palette(String, usize),
+    Help,
+}
+
+// The texture for blue-noise dithering is only read once parsing succeeds.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DitherArg {
+    None,
+    Diffusion(Kernel),
+    Bayer(usize),
+    Texture(String),
+}
+
+// Palette files and images are also only read once parsing succeeds.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PaletteArg {
+    Builtin(Palette),
+    File(String),
+    /// An image to pick this many colours from.
+    Image(String, usize),
+}
+
+#[derive(Clone, Debug)]
+pub struct Args {
+    pub inputs: Vec<String>,
+    pub width: usize,
+    pub height: usize,
+    pub fit_terminal: bool,
+    pub fit: Option<FitMode>,
+    pub format: OutputFormat,
+    pub color: ColorMode,
+    /// Detected from the environment when `None`.
+    pub depth: Option<ColorDepth>,
+    pub metric: ColorMetric,
+    pub palette: Option<PaletteArg>,
+    pub mode: CellMode,
+    pub ramp: Option<Vec<char>>,
+    pub ramp_font: Option<String>,
+    pub shape_font: Option<String>,
+    pub dither: DitherArg,
+    pub serpentine: bool,
+    pub filter: Filter,
+    pub color_space: ColorSpace,
+    pub animate: bool,
+    pub output: Option<String>,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            inputs: vec![],
+            width: 96,
+            height: 48,
+            fit_terminal: false,
+            fit: None,
+            format: OutputFormat::Ansi,
+            color: ColorMode::Full,
+            depth: None,
+            metric: ColorMetric::Euclidean,
+            palette: None,
+            mode: CellMode::Ramp,
+            ramp: None,
+            ramp_font: None,
+            shape_font: None,
+            dither: DitherArg::None,
+            serpentine: true,
+            filter: Filter::Box,
+            color_space: ColorSpace::Srgb,
+            animate: false,
+            output: None,
+        }
+    }
+}
+
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter().peekable();
+
+    if args.peek().map(String::as_str) == Some("demo") {
+        args.next();
+        let image = args.next().unwrap_or("assets/scene1.jpg".to_string());
+        if let Some(extra) = args.next() {
+            return Err(format!("unexpected argument '{}'", extra));
+        }
+        return Ok(Command::Demo(image));
+    }
+
+    if args.peek().map(String::as_str) == Some("ramp") {
+        args.next();
+        let font = args.next().ok_or("ramp expects a font file")?;
+        let chars = args.next();
+        if let Some(extra) = args.next() {
+            return Err(format!("unexpected argument '{}'", extra));
+        }
+        return Ok(Command::Ramp(font, chars));
+    }
+
+    if args.peek().map(String::as_str) == Some("palette") {
+        args.next();
+        let image = args.next().ok_or("palette expects an image")?;
+        let colors = match args.next() {
+            Some(n) => parse_size(&n)?,
+            None => 16,
+        };
+        if let Some(extra) = args.next() {
+            return Err(format!("unexpected argument '{}'", extra));
+        }
+        return Ok(Command::Palette(image, colors));
+    }
+
+    let mut parsed = Args::default();
+
+    while let Some(arg) = args.next() {
+        // Accept both `--width 80` and `--width=80`.
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg.clone(), None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{} expects a value", flag))
+        };
+
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-w" | "--width" => parsed.width = parse_size(&value()?)?,
+            "-H" | "--height" => parsed.height = parse_size(&value()?)?,
+            "-t" | "--fit-terminal" => parsed.fit_terminal = true,
+            "--fit" => parsed.fit = Some(parse_fit(&value()?)?),
+            "-f" | "--format" => {
+                parsed.format = match value()?.as_str() {
+                    "ansi" => OutputFormat::Ansi,
+                    "text" => OutputFormat::Text,
+                    "html" => OutputFormat::Html,
+                    other => return Err(format!("unknown output format '{}'", other)),
+                }
+            }
+            "-c" | "--color" => {
+                parsed.color = match value()?.as_str() {
+                    "full" => ColorMode::Full,
+                    "fg" => ColorMode::Foreground,
+                    "none" => ColorMode::None,
+                    other => return Err(format!("unknown colour mode '{}'", other)),
+                }
+            }
+            "--colors" => {
+                parsed.depth = match value()?.as_str() {
+                    "truecolor" | "24bit" => Some(ColorDepth::TrueColor),
+                    "256" => Some(ColorDepth::Ansi256),
+                    "16" => Some(ColorDepth::Ansi16),
+                    "auto" => None,
+                    other => return Err(format!("unknown colour depth '{}'", other)),
+                }
+            }
+            "--metric" => {
+                parsed.metric = match value()?.as_str() {
+                    "euclidean" => ColorMetric::Euclidean,
+                    "redmean" => ColorMetric::Redmean,
+                    "cie76" => ColorMetric::Cie76,
+                    "ciede2000" => ColorMetric::Ciede2000,
+                    other => return Err(format!("unknown colour metric '{}'", other)),
+                }
+            }
+            "-p" | "--palette" => parsed.palette = Some(parse_palette(&value()?)?),
+            "-m" | "--mode" => {
+                parsed.mode = match value()?.as_str() {
+                    "ramp" => CellMode::Ramp,
+                    "half" => CellMode::HalfBlock,
+                    "quadrant" => CellMode::Quadrant,
+                    "sextant" => CellMode::Sextant,
+                    "braille" => CellMode::Braille,
+                    "edge" => CellMode::Edge,
+                    "shape" => CellMode::Shape,
+                    other => return Err(format!("unknown cell mode '{}'", other)),
+                }
+            }
+            "-r" | "--ramp" => {
+                let ramp = value()?.chars().collect::<Vec<_>>();
+                if ramp.is_empty() {
+                    return Err("the glyph ramp cannot be empty".to_string());
+                }
+                parsed.ramp = Some(ramp);
+            }
+            "--ramp-font" => parsed.ramp_font = Some(value()?),
+            "--shape-font" => parsed.shape_font = Some(value()?),
+            "-d" | "--dither" => {
+                parsed.dither = parse_dither(&value()?)?;
+            }
+            "--no-serpentine" => parsed.serpentine = false,
+            "--filter" => {
+                parsed.filter = match value()?.as_str() {
+                    "box" => Filter::Box,
+                    "bilinear" => Filter::Bilinear,
+                    "lanczos" => Filter::Lanczos3,
+                    other => return Err(format!("unknown filter '{}'", other)),
+                }
+            }
+            "--linear" => parsed.color_space = ColorSpace::Linear,
+            "-a" | "--animate" => parsed.animate = true,
+            "-o" | "--output" => parsed.output = Some(value()?),
+            "-" => parsed.inputs.push(arg),
+            _ if flag.starts_with('-') => return Err(format!("unknown option '{}'", flag)),
+            _ => parsed.inputs.push(arg),
+        }
+    }
+
+    if parsed.inputs.is_empty() {
+        return Err("no input images given".to_string());
+    }
+
+    Ok(Command::Convert(parsed))
+}
+
+fn parse_size(value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(size) if size > 0 => Ok(size),
+        _ => Err(format!("'{}' is not a positive number", value)),
+    }
+}
+
+// A built-in palette by name, `IMAGE:N` for N colours picked from an image,
+// or a palette file.
+fn parse_palette(value: &str) -> Result<PaletteArg, String> {
+    if let Some(palette) = Palette::builtin(value) {
+        return Ok(PaletteArg::Builtin(palette));
+    }
+    match value.rsplit_once(':') {
+        Some((path, n)) if !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) => {
+            Ok(PaletteArg::Image(path.to_string(), parse_size(n)?))
+        }
+        _ => Ok(PaletteArg::File(value.to_string())),
+    }
+}
+
+// `contain` or `contain:#rrggbb` for the letterbox colour, `cover` or
+// `cover:<anchor>` for the crop anchor (e.g. `cover:top-left`).
+fn parse_fit(value: &str) -> Result<FitMode, String> {
+    let (mode, argument) = match value.split_once(':') {
+        Some((mode, argument)) => (mode, Some(argument)),
+        None => (value, None),
+    };
+
+    match (mode, argument) {
+        ("stretch", None) => Ok(FitMode::Stretch),
+        ("shrink", None) => Ok(FitMode::Shrink),
+        ("contain", None) => Ok(FitMode::Contain(Color::black())),
+        ("contain", Some(color)) => Ok(FitMode::Contain(parse_color(color)?)),
+        ("cover", None) => Ok(FitMode::Cover(Anchor::Center)),
+        ("cover", Some(anchor)) => Ok(FitMode::Cover(parse_anchor(anchor)?)),
+        _ => Err(format!("unknown fit mode '{}'", value)),
+    }
+}
+
+fn parse_dither(value: &str) -> Result<DitherArg, String> {
+    match value.split_once(':') {
+        Some(("bayer", size)) => match parse_size(size)? {
+            n if (2..=ThresholdMap::MAX_BAYER_SIZE).contains(&n) && n.is_power_of_two() => {
+                Ok(DitherArg::Bayer(n))
+            }
+            _ => Err(format!(
+                "Bayer matrix size '{}' is not a power of two from 2 to {}",
+                size,
+                ThresholdMap::MAX_BAYER_SIZE
+            )),
+        },
+        Some(("blue-noise", path)) if !path.is_empty() => Ok(DitherArg::Texture(path.to_string())),
+        None => match value {
+            "none" => Ok(DitherArg::None),
+            "floyd-steinberg" => Ok(DitherArg::Diffusion(Kernel::FloydSteinberg)),
+            "atkinson" => Ok(DitherArg::Diffusion(Kernel::Atkinson)),
+            "sierra" => Ok(DitherArg::Diffusion(Kernel::Sierra)),
+            "bayer" => Ok(DitherArg::Bayer(4)),
+            _ => Err(format!("unknown dithering '{}'", value)),
+        },
+        _ => Err(format!("unknown dithering '{}'", value)),
+    }
+}
+
+fn parse_color(value: &str) -> Result<Color, String> {
+    let hex = value.trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .ok_or_else(|| format!("'{}' is not a #rrggbb colour", value))
+    };
+    // `from_str_radix` alone would take a leading `+`.
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("'{}' is not a #rrggbb colour", value));
+    }
+    Ok(Color::new(channel(0)?, channel(2)?, channel(4)?))
+}
+
+fn parse_anchor(value: &str) -> Result<Anchor, String> {
+    match value {
+        "top-left" => Ok(Anchor::TopLeft),
+        "top" => Ok(Anchor::Top),
+        "top-right" => Ok(Anchor::TopRight),
+        "left" => Ok(Anchor::Left),
+        "center" => Ok(Anchor::Center),
+        "right" => Ok(Anchor::Right),
+        "bottom-left" => Ok(Anchor::BottomLeft),
+        "bottom" => Ok(Anchor::Bottom),
+        "bottom-right" => Ok(Anchor::BottomRight),
+        _ => Err(format!("unknown anchor '{}'", value)),
+    }
+}
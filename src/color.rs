@@ -0,0 +1,439 @@
+//! Colours and the colour spaces they are mixed in.
+
+use palette::{
+    color_difference::{Ciede2000, DeltaE},
+    rgb::Rgb,
+    FromColor, Hsv, Lab, LinSrgb, Srgb,
+};
+
+/// Where colours are mixed: directly on the sRGB bytes, or in linear light
+/// which keeps averaged and blended mid-tones at their real brightness.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSpace {
+    /// Mix the gamma-encoded sRGB bytes as they are.
+    Srgb,
+    /// Decode to linear light, mix, and encode again.
+    Linear,
+}
+
+/// How the difference between two colours is measured, both when matching
+/// colours against a palette and when deciding whether a cell has changed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorMetric {
+    /// Straight-line distance between the sRGB bytes.
+    Euclidean,
+    /// RGB distance weighted by how red the colours are, a cheap
+    /// approximation of how the eye weighs the channels.
+    Redmean,
+    /// Distance in CIE L*a*b*, where 1.0 is about the smallest visible step.
+    Cie76,
+    /// CIEDE2000, which corrects CIE76 for saturated colours and blues.
+    Ciede2000,
+}
+
+impl ColorMetric {
+    /// The difference between `a` and `b`; 0.0 when they are equal.
+    pub fn distance(&self, a: Color, b: Color) -> f64 {
+        match self {
+            ColorMetric::Euclidean => a.distance(b),
+            ColorMetric::Redmean => {
+                let mean = (a.red as f64 + b.red as f64) / 2.0;
+                let r = a.red as f64 - b.red as f64;
+                let g = a.green as f64 - b.green as f64;
+                let b = a.blue as f64 - b.blue as f64;
+                ((2.0 + mean / 256.0) * r * r + 4.0 * g * g + (2.0 + (255.0 - mean) / 256.0) * b * b)
+                    .sqrt()
+            }
+            ColorMetric::Cie76 => a.to_lab().delta_e(b.to_lab()) as f64,
+            ColorMetric::Ciede2000 => a.to_lab().difference(b.to_lab()) as f64,
+        }
+    }
+
+    /// A distance small enough that [`crate::Canvas::display`] can leave the
+    /// previous cell's colours in place, on this metric's scale.
+    pub fn tolerance(&self) -> f64 {
+        match self {
+            ColorMetric::Euclidean => 20.0,
+            ColorMetric::Redmean => 40.0,
+            ColorMetric::Cie76 => 6.0,
+            ColorMetric::Ciede2000 => 4.0,
+        }
+    }
+}
+
+// The six levels of each channel in the xterm 256-colour cube.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+// xterm's default values for the 16 standard colours, in ANSI order.
+const ANSI16: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// An 8-bit sRGB colour.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Color {
+    /// Red channel, 0 to 255.
+    pub red: u8,
+    /// Green channel, 0 to 255.
+    pub green: u8,
+    /// Blue channel, 0 to 255.
+    pub blue: u8,
+}
+
+impl Color {
+    /// Creates a colour from its sRGB channels.
+    pub fn new(red: u8, green: u8, blue: u8) -> Self {
+        Self { red, green, blue }
+    }
+
+    /// Snaps the colour to the closest of a handful of saturated primaries.
+    pub fn simplify(&self) -> Color {
+        let r = self.red;
+        let g = self.green;
+        let b = self.blue;
+
+        if r < 50 && g < 50 && b < 50 {
+            return Color::black();
+        }
+
+        if r > 205 && g > 205 && b > 205 {
+            return Color::white();
+        }
+
+        if r >= g && r >= b {
+            if r - g < 64 {
+                Color::yellow()
+            } else if r - b < 64 {
+                Color::magenta()
+            } else {
+                Color::red()
+            }
+        } else if g >= r && g >= b {
+            if g - b < 64 {
+                Color::cyan()
+            } else if g - r < 64 {
+                Color::yellow()
+            } else {
+                Color::green()
+            }
+        } else {
+            if b - r < 64 {
+                Color::magenta()
+            } else if b - g < 64 {
+                Color::cyan()
+            } else {
+                Color::blue()
+            }
+        }
+    }
+
+    /// The closest colour of the xterm 256-colour palette: either the 6x6x6
+    /// cube (16 to 231) or the 24-step grey ramp (232 to 255).
+    pub fn to_ansi256(&self, metric: ColorMetric) -> u8 {
+        let level = |v: u8| match v {
+            0..48 => 0,
+            48..115 => 1,
+            _ => (v - 35) / 40,
+        };
+        let (r, g, b) = (level(self.red), level(self.green), level(self.blue));
+        let cube = Color::new(
+            CUBE_LEVELS[r as usize],
+            CUBE_LEVELS[g as usize],
+            CUBE_LEVELS[b as usize],
+        );
+
+        // Greys run from 8 to 238 in steps of 10.
+        let grey = ((self.brightness() - 3.0) / 10.0).clamp(0.0, 23.0) as u8;
+        let value = 8 + 10 * grey;
+
+        if metric.distance(*self, Color::new(value, value, value)) < metric.distance(*self, cube) {
+            232 + grey
+        } else {
+            16 + 36 * r + 6 * g + b
+        }
+    }
+
+    /// Like [`Color::simplify`], but snaps to the closest of the 16 standard
+    /// ANSI colours (as xterm shows them) and returns its index, 0 to 15.
+    pub fn to_ansi16(&self, metric: ColorMetric) -> u8 {
+        let distance = |&(r, g, b): &(u8, u8, u8)| metric.distance(*self, Color::new(r, g, b));
+        (0..16)
+            .min_by(|&a, &b| distance(&ANSI16[a]).total_cmp(&distance(&ANSI16[b])))
+            .unwrap() as u8
+    }
+
+    /// Offsets every channel and clamps it to `min..=max`.
+    pub fn clamp(&self, min: i32, max: i32, offset: i32) -> Color {
+        let r = (self.red as i32 + offset).clamp(min, max);
+        let g = (self.green as i32 + offset).clamp(min, max);
+        let b = (self.blue as i32 + offset).clamp(min, max);
+        Color::new(r as u8, g as u8, b as u8)
+    }
+
+    /// Quantises every channel down to a multiple of `strength`.
+    pub fn blockify(&self, strength: u8) -> Color {
+        let r = self.red as f32;
+        let g = self.green as f32;
+        let b = self.blue as f32;
+        let r = (r / strength as f32) as u8 * strength;
+        let g = (g / strength as f32) as u8 * strength;
+        let b = (b / strength as f32) as u8 * strength;
+        Color::new(r, g, b)
+    }
+
+    /// Splits the colour into the foreground and background used for a cell,
+    /// plus how bright it is (0.0 to 1.0) for picking the glyph.
+    pub fn decompose(self) -> (Color, Color, f32) {
+        //let c1 = self.shift(self.blockify(16), 0.5);
+        //let c2 = c1.shift(c1.simplify(), 0.8);
+        let c1 = self.blockify(16).shift(Color::black(), 0.7);
+        let c2 = self.blockify(16);
+
+        /*
+        let x = (c1.red as f32 + c1.green as f32 + c1.blue as f32) as f32;
+        let y = (c2.red as f32 + c2.green as f32 + c2.blue as f32) as f32;
+        let res = (r as f32 + g as f32 + b as f32)
+            + (x + y)
+            / ((x + y - self.brightness() as f32).abs() / 20.0);
+        let weight = ((res - x) / (y - x)).clamp(0.0, 1.0);
+        */
+
+        let weight = self.brightness() as f32 / 255.0;
+
+        (c2, c1, weight)
+    }
+
+    /// A slightly muted red, used by [`Color::simplify`].
+    pub fn red() -> Color {
+        Color::new(225, 30, 30)
+    }
+
+    /// A slightly muted yellow, used by [`Color::simplify`].
+    pub fn yellow() -> Color {
+        Color::new(225, 225, 30)
+    }
+
+    /// A slightly muted magenta, used by [`Color::simplify`].
+    pub fn magenta() -> Color {
+        Color::new(225, 30, 225)
+    }
+
+    /// A slightly muted green, used by [`Color::simplify`].
+    pub fn green() -> Color {
+        Color::new(30, 225, 30)
+    }
+
+    /// A slightly muted cyan, used by [`Color::simplify`].
+    pub fn cyan() -> Color {
+        Color::new(30, 225, 225)
+    }
+
+    /// A slightly muted blue, used by [`Color::simplify`].
+    pub fn blue() -> Color {
+        Color::new(30, 30, 225)
+    }
+
+    /// Near-black, a touch lifted off pure black.
+    pub fn black() -> Color {
+        Color::new(10, 10, 10)
+    }
+
+    /// Off-white, a touch below pure white.
+    pub fn white() -> Color {
+        Color::new(225, 225, 225)
+    }
+
+    /// Euclidean distance between the two colours in RGB space.
+    pub fn distance(&self, other: Color) -> f64 {
+        let r1 = self.red as f64;
+        let g1 = self.green as f64;
+        let b1 = self.blue as f64;
+        let r2 = other.red as f64;
+        let g2 = other.green as f64;
+        let b2 = other.blue as f64;
+        let r = (r1 - r2).powf(2.0);
+        let g = (g1 - g2).powf(2.0);
+        let b = (b1 - b2).powf(2.0);
+        (r + g + b).sqrt()
+    }
+
+    /// The channel-wise mean of `colors` taken in `space`, or black when
+    /// there are none.
+    pub fn average(colors: &[Color], space: ColorSpace) -> Color {
+        if colors.is_empty() {
+            return Color::black();
+        }
+        let sum = colors.iter().fold([0.0f32; 3], |sum, c| {
+            let c = c.channels(space);
+            [sum[0] + c[0], sum[1] + c[1], sum[2] + c[2]]
+        });
+        let n = colors.len() as f32;
+        Color::from_channels(sum.map(|s| s / n), space)
+    }
+
+    /// The channels in `space`, each 0.0 to 255.0.
+    pub fn channels(self, space: ColorSpace) -> [f32; 3] {
+        match space {
+            ColorSpace::Srgb => [self.red as f32, self.green as f32, self.blue as f32],
+            ColorSpace::Linear => {
+                let linear = self.to_linear();
+                [linear.red * 255.0, linear.green * 255.0, linear.blue * 255.0]
+            }
+        }
+    }
+
+    /// The inverse of [`Color::channels`], rounding and clamping each channel.
+    pub fn from_channels([r, g, b]: [f32; 3], space: ColorSpace) -> Self {
+        match space {
+            ColorSpace::Srgb => {
+                let [r, g, b] = [r, g, b].map(|v| v.round().clamp(0.0, 255.0) as u8);
+                Color::new(r, g, b)
+            }
+            ColorSpace::Linear => Color::from_linear(LinSrgb::new(r / 255.0, g / 255.0, b / 255.0)),
+        }
+    }
+
+    /// Mean of the three channels, 0.0 to 255.0.
+    pub fn brightness(&self) -> f64 {
+        (self.red as f64 + self.green as f64 + self.blue as f64) / 3.0
+    }
+
+    /// Mixes `power` of `other` into this colour, on the sRGB bytes.
+    pub fn shift(&self, other: Color, power: f32) -> Color {
+        Color::new(
+            (self.red as f32 * (1.0 - power) + other.red as f32 * power) as u8,
+            (self.green as f32 * (1.0 - power) + other.green as f32 * power) as u8,
+            (self.blue as f32 * (1.0 - power) + other.blue as f32 * power) as u8,
+        )
+    }
+
+    /// Like [`Color::shift`], but mixes in linear light.
+    pub fn shift_linear(&self, other: Color, power: f32) -> Color {
+        Color::from_linear(self.to_linear() * (1.0 - power) + other.to_linear() * power)
+    }
+
+    /// Mixes `power` of `other` into this colour in the given colour space.
+    pub fn blend(&self, other: Color, power: f32, space: ColorSpace) -> Color {
+        match space {
+            ColorSpace::Srgb => self.shift(other, power),
+            ColorSpace::Linear => self.shift_linear(other, power),
+        }
+    }
+
+    /// Decodes the colour to linear light.
+    pub fn to_linear(self) -> LinSrgb {
+        Srgb::new(self.red, self.green, self.blue).into_linear()
+    }
+
+    /// Encodes a linear-light colour back to 8-bit sRGB.
+    pub fn from_linear(linear: LinSrgb) -> Self {
+        let rgb = Srgb::<u8>::from_linear(linear);
+        Self::new(rgb.red, rgb.green, rgb.blue)
+    }
+
+    /// The colour in CIE L*a*b*, with a D65 white point.
+    pub fn to_lab(self) -> Lab {
+        Lab::from_color(Srgb::new(self.red, self.green, self.blue).into_format::<f32>())
+    }
+
+    /// The colour as hue, saturation and value, computed on the sRGB channels.
+    pub fn to_hsv(self) -> Hsv {
+        Hsv::from_color(Rgb::new(
+            self.red as f32 / 255.0,
+            self.green as f32 / 255.0,
+            self.blue as f32 / 255.0,
+        ))
+    }
+
+    /// The inverse of [`Color::to_hsv`], truncating each channel.
+    pub fn from_hsv(hsv: Hsv) -> Self {
+        let rgb = Rgb::from_color(hsv);
+        Self::new(
+            (rgb.red * 255.0) as u8,
+            (rgb.green * 255.0) as u8,
+            (rgb.blue * 255.0) as u8,
+        )
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn average_in_either_space() {
+        let colors = [Color::new(0, 0, 0), Color::new(255, 255, 255)];
+        assert_eq!(Color::average(&colors, ColorSpace::Srgb), Color::new(128, 128, 128));
+        // Half the light of white is much brighter than half its sRGB value.
+        assert_eq!(Color::average(&colors, ColorSpace::Linear), Color::new(188, 188, 188));
+        assert_eq!(Color::average(&[], ColorSpace::Linear), Color::black());
+    }
+
+    #[test]
+    fn ansi256_cube_levels() {
+        // Each channel snaps to the nearest of 0, 95, 135, 175, 215 and 255.
+        let cases = [
+            (0, 0),
+            (47, 0),
+            (48, 1),
+            (95, 1),
+            (114, 1),
+            (115, 2),
+            (135, 2),
+            (154, 2),
+            (155, 3),
+            (194, 3),
+            (195, 4),
+            (234, 4),
+            (235, 5),
+            (255, 5),
+        ];
+        for (value, level) in cases {
+            let code = Color::new(value, 0, 255).to_ansi256(ColorMetric::Euclidean);
+            assert_eq!(code, 16 + 36 * level + 5, "red {}", value);
+        }
+        assert_eq!(Color::new(95, 135, 175).to_ansi256(ColorMetric::Euclidean), 67);
+    }
+
+    #[test]
+    fn ansi256_grey_ramp() {
+        let grey = |v: u8| Color::new(v, v, v).to_ansi256(ColorMetric::Euclidean);
+        // Black and white are closer to the cube's corners than to any grey.
+        assert_eq!(grey(0), 16);
+        assert_eq!(grey(255), 231);
+        // The ramp runs 8, 18, ..., 238, switching halfway between steps.
+        assert_eq!(grey(8), 232);
+        assert_eq!(grey(12), 232);
+        assert_eq!(grey(13), 233);
+        assert_eq!(grey(128), 244);
+        assert_eq!(grey(238), 255);
+    }
+
+    #[test]
+    fn ansi16_nearest() {
+        let ansi16 = |r, g, b| Color::new(r, g, b).to_ansi16(ColorMetric::Euclidean);
+        assert_eq!(ansi16(0, 0, 0), 0);
+        assert_eq!(ansi16(200, 0, 0), 1);
+        assert_eq!(ansi16(250, 10, 10), 9);
+        assert_eq!(ansi16(120, 120, 120), 8);
+        assert_eq!(ansi16(230, 230, 230), 7);
+        assert_eq!(ansi16(90, 90, 250), 12);
+        assert_eq!(ansi16(0, 200, 210), 6);
+    }
+}
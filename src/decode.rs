@@ -0,0 +1,859 @@
+//! Decoding JPEG, PNG, GIF, BMP and PNM files into RGBA pixels.
+
+use std::{fmt, io, io::Cursor, time::Duration};
+
+use jpeg_decoder::{Decoder as JpegDecoder, PixelFormat};
+
+use crate::exif;
+
+// The largest GIF screen accepted. Its size costs nothing in the file, so
+// unlike the other formats it can't be checked against the data.
+const MAX_GIF_PIXELS: usize = 1 << 26;
+
+/// The image formats [`decode`] understands.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// JPEG, baseline or progressive.
+    Jpeg,
+    /// PNG, at any bit depth.
+    Png,
+    /// GIF, still or animated.
+    Gif,
+    /// Windows BMP.
+    Bmp,
+    /// Netpbm PBM, PGM and PPM, in ASCII or binary.
+    Pnm,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Format::Jpeg => "JPEG",
+            Format::Png => "PNG",
+            Format::Gif => "GIF",
+            Format::Bmp => "BMP",
+            Format::Pnm => "PNM",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Format {
+    /// Detects the format from the file's magic bytes.
+    pub fn sniff(bytes: &[u8]) -> Option<Format> {
+        if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Format::Jpeg)
+        } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Format::Png)
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            Some(Format::Gif)
+        } else if bytes.starts_with(b"BM") {
+            Some(Format::Bmp)
+        } else if bytes.len() >= 2 && bytes[0] == b'P' && (b'1'..=b'6').contains(&bytes[1]) {
+            Some(Format::Pnm)
+        } else {
+            None
+        }
+    }
+}
+
+/// Why an image could not be turned into a canvas.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The data looked like `Format` but was corrupt or used an unsupported feature.
+    Decode(Format, String),
+    /// The data matched none of the known formats.
+    UnsupportedFormat(String),
+    /// The image or the requested canvas has no pixels.
+    ZeroSize,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "could not read image: {}", err),
+            LoadError::Decode(format, message) => {
+                write!(f, "could not decode {} image: {}", format, message)
+            }
+            LoadError::UnsupportedFormat(what) => write!(f, "unsupported image format: {}", what),
+            LoadError::ZeroSize => write!(f, "image or canvas has zero width or height"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> Self {
+        LoadError::Io(err)
+    }
+}
+
+fn corrupt(format: Format, message: &str) -> LoadError {
+    LoadError::Decode(format, message.to_string())
+}
+
+/// Decoded pixels in row-major RGBA order, regardless of the source format.
+#[derive(Clone, Debug)]
+pub struct Image {
+    /// Width in pixels.
+    pub width: usize,
+    /// Height in pixels.
+    pub height: usize,
+    /// `width * height` pixels, top row first.
+    pub pixels: Vec<[u8; 4]>,
+}
+
+impl Image {
+    /// A fully transparent black image of the given size.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![[0, 0, 0, 0]; width * height],
+        }
+    }
+
+    /// The pixel at column `x`, row `y`. Panics when out of bounds.
+    pub fn get(&self, x: usize, y: usize) -> [u8; 4] {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Overwrites the pixel at column `x`, row `y`. Panics when out of bounds.
+    pub fn set(&mut self, x: usize, y: usize, pixel: [u8; 4]) {
+        self.pixels[y * self.width + x] = pixel;
+    }
+
+    /// Undoes an EXIF orientation (1-8) so the image appears upright. Other
+    /// values leave the image as it is.
+    pub fn orient(&self, orientation: u16) -> Image {
+        let (w, h) = (self.width, self.height);
+        let transposed = (5..=8).contains(&orientation);
+        let (width, height) = if transposed { (h, w) } else { (w, h) };
+        let mut image = Image::new(width, height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = match orientation {
+                    2 => (w - 1 - x, y),
+                    3 => (w - 1 - x, h - 1 - y),
+                    4 => (x, h - 1 - y),
+                    5 => (y, x),
+                    6 => (y, h - 1 - x),
+                    7 => (w - 1 - y, h - 1 - x),
+                    8 => (w - 1 - y, x),
+                    _ => (x, y),
+                };
+                image.set(x, y, self.get(sx, sy));
+            }
+        }
+
+        image
+    }
+
+    /// Copies out the `width` x `height` region starting at `x`, `y`.
+    pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Image {
+        let pixels = (y..y + height)
+            .flat_map(|row| &self.pixels[row * self.width + x..row * self.width + x + width])
+            .copied()
+            .collect();
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+/// Decodes an image of any supported format, using its first frame.
+pub fn decode(bytes: &[u8]) -> Result<Image, LoadError> {
+    let format = Format::sniff(bytes)
+        .ok_or_else(|| LoadError::UnsupportedFormat("unrecognised file signature".to_string()))?;
+
+    let image = match format {
+        Format::Jpeg => decode_jpeg(bytes)?,
+        Format::Png => decode_png(bytes)?,
+        Format::Gif => decode_gif(bytes)?,
+        Format::Bmp => decode_bmp(bytes)?,
+        Format::Pnm => decode_pnm(bytes)?,
+    };
+
+    if image.width == 0 || image.height == 0 {
+        return Err(LoadError::ZeroSize);
+    }
+
+    Ok(image)
+}
+
+fn decode_jpeg(bytes: &[u8]) -> Result<Image, LoadError> {
+    let mut decoder = JpegDecoder::new(Cursor::new(bytes));
+
+    let pixels = decoder
+        .decode()
+        .map_err(|err| LoadError::Decode(Format::Jpeg, err.to_string()))?;
+    let metadata = decoder.info().ok_or_else(|| corrupt(Format::Jpeg, "missing frame header"))?;
+
+    let pixels = match metadata.pixel_format {
+        PixelFormat::L8 => pixels.iter().map(|l| [*l, *l, *l, 255]).collect(),
+        PixelFormat::L16 => {
+            let samples = pixels
+                .chunks(2)
+                .map(|p| u16::from_ne_bytes([p[0], p[1]]))
+                .collect::<Vec<_>>();
+            // The decoder does not expose the sample precision, so read it
+            // from the frame header ourselves.
+            let shift = jpeg_precision(bytes).unwrap_or(16).saturating_sub(8);
+            samples
+                .iter()
+                .map(|l| {
+                    let l = (l >> shift) as u8;
+                    [l, l, l, 255]
+                })
+                .collect()
+        }
+        PixelFormat::RGB24 => pixels.chunks(3).map(|p| [p[0], p[1], p[2], 255]).collect(),
+        // The decoder already undoes the Adobe inversion, so these are ink amounts.
+        PixelFormat::CMYK32 => pixels
+            .chunks(4)
+            .map(|p| {
+                let k = 255 - p[3] as u16;
+                let channel = |ink: u8| ((255 - ink as u16) * k / 255) as u8;
+                [channel(p[0]), channel(p[1]), channel(p[2]), 255]
+            })
+            .collect(),
+    };
+
+    Ok(Image {
+        width: metadata.width as usize,
+        height: metadata.height as usize,
+        pixels,
+    })
+}
+
+// The bits per sample (8, 12 or 16) in a JPEG's start-of-frame header.
+fn jpeg_precision(bytes: &[u8]) -> Option<u8> {
+    exif::segments(bytes)
+        // SOF0 to SOF15, except DHT (C4), JPG (C8) and DAC (CC).
+        .find(|(marker, _)| (0xC0..=0xCF).contains(marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC))
+        .and_then(|(_, segment)| segment.first().copied())
+}
+
+fn decode_png(bytes: &[u8]) -> Result<Image, LoadError> {
+    let mut decoder = png::Decoder::new(Cursor::new(bytes));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let error = |err: png::DecodingError| LoadError::Decode(Format::Png, err.to_string());
+    let mut reader = decoder.read_info().map_err(error)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(error)?;
+
+    let channels = info.color_type.samples();
+    let mut image = Image::new(info.width as usize, info.height as usize);
+
+    for (y, line) in buffer.chunks(info.line_size).take(image.height).enumerate() {
+        for (x, p) in line.chunks(channels).take(image.width).enumerate() {
+            let pixel = match info.color_type {
+                png::ColorType::Grayscale => [p[0], p[0], p[0], 255],
+                png::ColorType::GrayscaleAlpha => [p[0], p[0], p[0], p[1]],
+                png::ColorType::Rgb => [p[0], p[1], p[2], 255],
+                png::ColorType::Rgba => [p[0], p[1], p[2], p[3]],
+                png::ColorType::Indexed => {
+                    return Err(corrupt(Format::Png, "palette was not expanded"))
+                }
+            };
+            image.set(x, y, pixel);
+        }
+    }
+
+    Ok(image)
+}
+
+fn decode_gif(bytes: &[u8]) -> Result<Image, LoadError> {
+    let (image, _) = gif_frames(bytes, Some(1))?.remove(0);
+    Ok(image)
+}
+
+/// Decodes every frame of an animated GIF, fully composited, with its delay.
+pub fn decode_gif_frames(bytes: &[u8]) -> Result<Vec<(Image, Duration)>, LoadError> {
+    match Format::sniff(bytes) {
+        Some(Format::Gif) => gif_frames(bytes, None),
+        _ => Err(LoadError::UnsupportedFormat("animations must be GIF files".to_string())),
+    }
+}
+
+fn gif_frames(bytes: &[u8], limit: Option<usize>) -> Result<Vec<(Image, Duration)>, LoadError> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::RGBA);
+
+    let error = |err: gif::DecodingError| LoadError::Decode(Format::Gif, err.to_string());
+    let mut decoder = options.read_info(Cursor::new(bytes)).map_err(error)?;
+    let (screen_width, screen_height) = (decoder.width() as usize, decoder.height() as usize);
+    if screen_width * screen_height > MAX_GIF_PIXELS {
+        return Err(corrupt(Format::Gif, "image too large"));
+    }
+    let mut screen = Image::new(screen_width, screen_height);
+    let mut frames = vec![];
+
+    while limit.is_none_or(|limit| frames.len() < limit) {
+        let Some(frame) = decoder.read_next_frame().map_err(error)? else {
+            break;
+        };
+
+        let (left, top) = (frame.left as usize, frame.top as usize);
+        let (width, height) = (frame.width as usize, frame.height as usize);
+        // Only "restore to previous" needs the screen as it was before this frame.
+        let previous = matches!(frame.dispose, gif::DisposalMethod::Previous).then(|| screen.clone());
+
+        for (i, p) in frame.buffer.chunks(4).enumerate() {
+            let (x, y) = (left + i % width, top + i / width);
+            if x < screen.width && y < screen.height && p[3] != 0 {
+                screen.set(x, y, [p[0], p[1], p[2], p[3]]);
+            }
+        }
+
+        // Browsers treat tiny delays as "unspecified" and slow them down too.
+        let delay = match frame.delay {
+            0 | 1 => Duration::from_millis(100),
+            delay => Duration::from_millis(delay as u64 * 10),
+        };
+        frames.push((screen.clone(), delay));
+
+        match frame.dispose {
+            gif::DisposalMethod::Background => {
+                for y in top..(top + height).min(screen.height) {
+                    for x in left..(left + width).min(screen.width) {
+                        screen.set(x, y, [0, 0, 0, 0]);
+                    }
+                }
+            }
+            gif::DisposalMethod::Previous => screen = previous.unwrap_or(screen),
+            gif::DisposalMethod::Keep | gif::DisposalMethod::Any => {}
+        }
+    }
+
+    if frames.is_empty() {
+        return Err(corrupt(Format::Gif, "no frames"));
+    }
+
+    Ok(frames)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, LoadError> {
+    let b = bytes
+        .get(offset..offset + 2)
+        .ok_or_else(|| corrupt(Format::Bmp, "truncated header"))?;
+    Ok(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, LoadError> {
+    let b = bytes
+        .get(offset..offset + 4)
+        .ok_or_else(|| corrupt(Format::Bmp, "truncated header"))?;
+    Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+// Scales a masked channel (e.g. the 5 bits of a 565 green) up to 0..=255.
+fn extract_channel(value: u32, mask: u32) -> Option<u8> {
+    if mask == 0 {
+        return None;
+    }
+    let shift = mask.trailing_zeros();
+    let max = mask >> shift;
+    Some((((value & mask) >> shift) * 255 / max) as u8)
+}
+
+fn decode_bmp(bytes: &[u8]) -> Result<Image, LoadError> {
+    let data_offset = read_u32(bytes, 10)? as usize;
+    let header_size = read_u32(bytes, 14)? as usize;
+
+    let (width, height, bpp, compression, palette_size) = if header_size == 12 {
+        let width = read_u16(bytes, 18)? as i32;
+        let height = read_u16(bytes, 20)? as i16 as i32;
+        let bpp = read_u16(bytes, 24)?;
+        (width, height, bpp, 0, 0)
+    } else if header_size >= 40 {
+        let width = read_u32(bytes, 18)? as i32;
+        let height = read_u32(bytes, 22)? as i32;
+        let bpp = read_u16(bytes, 28)?;
+        let compression = read_u32(bytes, 30)?;
+        let colors_used = read_u32(bytes, 46)? as usize;
+        (width, height, bpp, compression, colors_used)
+    } else {
+        return Err(LoadError::UnsupportedFormat(format!(
+            "BMP with a {} byte header",
+            header_size
+        )));
+    };
+
+    // 0 = BI_RGB, 3 = BI_BITFIELDS, 6 = BI_ALPHABITFIELDS. RLE variants are not handled.
+    if !matches!(compression, 0 | 3 | 6) {
+        return Err(LoadError::UnsupportedFormat(format!(
+            "BMP compression method {}",
+            compression
+        )));
+    }
+    if !matches!(bpp, 1 | 2 | 4 | 8 | 16 | 24 | 32) {
+        return Err(LoadError::UnsupportedFormat(format!("{} bit BMP", bpp)));
+    }
+    if width <= 0 || height == 0 {
+        return Err(LoadError::ZeroSize);
+    }
+
+    let top_down = height < 0;
+    let width = width as usize;
+    let height = height.unsigned_abs() as usize;
+
+    let masks = match (compression, bpp) {
+        (3 | 6, _) if header_size >= 52 => [
+            read_u32(bytes, 54)?,
+            read_u32(bytes, 58)?,
+            read_u32(bytes, 62)?,
+            if header_size >= 56 || compression == 6 { read_u32(bytes, 66)? } else { 0 },
+        ],
+        (3 | 6, _) => [
+            read_u32(bytes, 14 + header_size)?,
+            read_u32(bytes, 18 + header_size)?,
+            read_u32(bytes, 22 + header_size)?,
+            if compression == 6 { read_u32(bytes, 26 + header_size)? } else { 0 },
+        ],
+        (_, 16) => [0x7C00, 0x03E0, 0x001F, 0],
+        (_, 32) => [0x00FF0000, 0x0000FF00, 0x000000FF, 0],
+        _ => [0, 0, 0, 0],
+    };
+
+    let palette = if bpp <= 8 {
+        let entry_size = if header_size == 12 { 3 } else { 4 };
+        let count = if palette_size == 0 { 1 << bpp } else { palette_size };
+        let start = 14 + header_size;
+        (0..count)
+            .map(|i| {
+                let b = bytes.get(start + i * entry_size..start + i * entry_size + 3)?;
+                Some([b[2], b[1], b[0], 255])
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| corrupt(Format::Bmp, "truncated palette"))?
+    } else {
+        vec![]
+    };
+
+    // Make sure the header's size is backed by pixel data before allocating for it.
+    let stride = width
+        .checked_mul(bpp as usize)
+        .map(|bits| bits.div_ceil(32) * 4)
+        .ok_or_else(|| corrupt(Format::Bmp, "truncated pixel data"))?;
+    let end = stride.checked_mul(height).and_then(|size| size.checked_add(data_offset));
+    if end.is_none_or(|end| end > bytes.len()) {
+        return Err(corrupt(Format::Bmp, "truncated pixel data"));
+    }
+    let mut image = Image::new(width, height);
+
+    for row in 0..height {
+        let y = if top_down { row } else { height - 1 - row };
+        let line = bytes
+            .get(data_offset + row * stride..data_offset + (row + 1) * stride)
+            .ok_or_else(|| corrupt(Format::Bmp, "truncated pixel data"))?;
+
+        for x in 0..width {
+            let pixel = match bpp {
+                1 | 2 | 4 | 8 => {
+                    let bit = x * bpp as usize;
+                    let byte = line[bit / 8];
+                    let shift = 8 - bpp as usize - bit % 8;
+                    let index = (byte >> shift) as usize & ((1 << bpp) - 1);
+                    *palette
+                        .get(index)
+                        .ok_or_else(|| corrupt(Format::Bmp, "palette index out of range"))?
+                }
+                24 => [line[x * 3 + 2], line[x * 3 + 1], line[x * 3], 255],
+                16 | 32 => {
+                    let value = if bpp == 16 {
+                        u16::from_le_bytes([line[x * 2], line[x * 2 + 1]]) as u32
+                    } else {
+                        u32::from_le_bytes([line[x * 4], line[x * 4 + 1], line[x * 4 + 2], line[x * 4 + 3]])
+                    };
+                    let channel = |mask| {
+                        extract_channel(value, mask)
+                            .ok_or_else(|| corrupt(Format::Bmp, "empty colour mask"))
+                    };
+                    [
+                        channel(masks[0])?,
+                        channel(masks[1])?,
+                        channel(masks[2])?,
+                        extract_channel(value, masks[3]).unwrap_or(255),
+                    ]
+                }
+                _ => unreachable!(),
+            };
+            image.set(x, y, pixel);
+        }
+    }
+
+    Ok(image)
+}
+
+// Splits a PNM header into whitespace separated tokens, skipping `#` comments.
+// Returns the tokens and the offset just past the last one.
+fn pnm_tokens(bytes: &[u8], start: usize, count: usize) -> Option<(Vec<usize>, usize)> {
+    let mut tokens = vec![];
+    let mut i = start;
+
+    while tokens.len() < count {
+        match bytes.get(i)? {
+            b'#' => {
+                while *bytes.get(i)? != b'\n' {
+                    i += 1;
+                }
+            }
+            c if c.is_ascii_whitespace() => i += 1,
+            _ => {
+                let start = i;
+                while bytes.get(i).is_some_and(|c| c.is_ascii_digit()) {
+                    i += 1;
+                }
+                if start == i {
+                    return None;
+                }
+                tokens.push(std::str::from_utf8(&bytes[start..i]).ok()?.parse().ok()?);
+            }
+        }
+    }
+
+    Some((tokens, i))
+}
+
+fn decode_pnm(bytes: &[u8]) -> Result<Image, LoadError> {
+    let kind = bytes[1];
+    let bitmap = kind == b'1' || kind == b'4';
+    let (header, end) = pnm_tokens(bytes, 2, if bitmap { 2 } else { 3 })
+        .ok_or_else(|| corrupt(Format::Pnm, "malformed header"))?;
+    let (width, height) = (header[0], header[1]);
+    let max = if bitmap { 1 } else { header[2] };
+
+    if width == 0 || height == 0 {
+        return Err(LoadError::ZeroSize);
+    }
+    if max == 0 || max > 65535 {
+        return Err(corrupt(Format::Pnm, "maximum sample value out of range"));
+    }
+
+    let truncated = || corrupt(Format::Pnm, "truncated pixel data");
+    // Binary rasters start after the single whitespace byte ending the header.
+    let data = bytes.get(end + 1..).unwrap_or(&[]);
+
+    let channels = match kind {
+        b'3' | b'6' => 3,
+        _ => 1,
+    };
+    let count = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(channels))
+        .ok_or_else(|| corrupt(Format::Pnm, "image too large"))?;
+
+    // Binary rasters have a known size, so check it is all there up front.
+    let size = match kind {
+        b'4' => width.div_ceil(8).checked_mul(height),
+        b'5' | b'6' if max >= 256 => count.checked_mul(2),
+        b'5' | b'6' => Some(count),
+        _ => Some(0),
+    };
+    if size.is_none_or(|size| size > data.len()) {
+        return Err(truncated());
+    }
+
+    let samples: Vec<usize> = match kind {
+        b'1' => bytes[end..]
+            .iter()
+            .filter(|c| **c == b'0' || **c == b'1')
+            .take(count)
+            .map(|c| (*c == b'0') as usize)
+            .collect(),
+        b'2' | b'3' => pnm_tokens(bytes, end, count).ok_or_else(truncated)?.0,
+        b'4' => {
+            let stride = width.div_ceil(8);
+            (0..count)
+                .map(|i| {
+                    let (x, y) = (i % width, i / width);
+                    let byte = *data.get(y * stride + x / 8)?;
+                    Some((byte >> (7 - x % 8) & 1 == 0) as usize)
+                })
+                .collect::<Option<_>>()
+                .ok_or_else(truncated)?
+        }
+        _ => {
+            if max < 256 {
+                data.get(..count).ok_or_else(truncated)?.iter().map(|b| *b as usize).collect()
+            } else {
+                data.get(..count * 2)
+                    .ok_or_else(truncated)?
+                    .chunks(2)
+                    .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+                    .collect()
+            }
+        }
+    };
+
+    if samples.len() < count {
+        return Err(truncated());
+    }
+
+    let scale = |v: usize| (v.min(max) * 255 / max) as u8;
+    let pixels = samples
+        .chunks(channels)
+        .map(|p| match p {
+            [v] => [scale(*v), scale(*v), scale(*v), 255],
+            _ => [scale(p[0]), scale(p[1]), scale(p[2]), 255],
+        })
+        .collect();
+
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A BMP with a 40 byte header followed by `masks`, then `data`.
+    fn bmp(
+        width: i32,
+        height: i32,
+        bpp: u16,
+        compression: u32,
+        masks: &[u32],
+        data: &[u8],
+    ) -> Vec<u8> {
+        let offset = 54 + masks.len() as u32 * 4;
+        let mut bytes = b"BM".to_vec();
+        bytes.extend((offset + data.len() as u32).to_le_bytes());
+        bytes.extend([0; 4]);
+        bytes.extend(offset.to_le_bytes());
+        bytes.extend(40u32.to_le_bytes());
+        bytes.extend(width.to_le_bytes());
+        bytes.extend(height.to_le_bytes());
+        bytes.extend(1u16.to_le_bytes());
+        bytes.extend(bpp.to_le_bytes());
+        bytes.extend(compression.to_le_bytes());
+        bytes.extend([0; 20]);
+        for mask in masks {
+            bytes.extend(mask.to_le_bytes());
+        }
+        bytes.extend(data);
+        bytes
+    }
+
+    fn rgb(image: &Image) -> Vec<[u8; 3]> {
+        image.pixels.iter().map(|&[r, g, b, _]| [r, g, b]).collect()
+    }
+
+    // A frame of `pixels` placed at `left`, `top`, with one palette entry per
+    // pixel so the colours come back exactly.
+    fn frame(left: u16, top: u16, width: u16, dispose: gif::DisposalMethod, pixels: &[[u8; 4]]) -> gif::Frame<'static> {
+        let transparent = pixels.len() as u8;
+        let mut palette = pixels.iter().flat_map(|p| [p[0], p[1], p[2]]).collect::<Vec<_>>();
+        palette.extend([0, 0, 0]);
+        let buffer = (0..pixels.len())
+            .map(|i| if pixels[i][3] == 0 { transparent } else { i as u8 })
+            .collect::<Vec<_>>();
+        gif::Frame {
+            left,
+            top,
+            width,
+            height: (pixels.len() / width as usize) as u16,
+            dispose,
+            transparent: Some(transparent),
+            palette: Some(palette),
+            buffer: buffer.into(),
+            ..gif::Frame::default()
+        }
+    }
+
+    fn gif(width: u16, height: u16, frames: &[gif::Frame]) -> Vec<u8> {
+        let mut bytes = vec![];
+        let mut encoder = gif::Encoder::new(&mut bytes, width, height, &[]).unwrap();
+        for frame in frames {
+            encoder.write_frame(frame).unwrap();
+        }
+        drop(encoder);
+        bytes
+    }
+
+    #[test]
+    fn gif_screen_size_is_limited() {
+        let bytes = gif(65535, 65535, &[frame(0, 0, 1, gif::DisposalMethod::Keep, &[[255; 4]])]);
+        assert!(matches!(decode(&bytes), Err(LoadError::Decode(Format::Gif, _))));
+    }
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    fn gif_pixels(bytes: &[u8]) -> Vec<Vec<[u8; 4]>> {
+        decode_gif_frames(bytes).unwrap().into_iter().map(|(image, _)| image.pixels).collect()
+    }
+
+    #[test]
+    fn gif_background_disposal_clears_the_frame() {
+        use gif::DisposalMethod::*;
+        let bytes = gif(2, 1, &[frame(0, 0, 2, Background, &[RED, RED]), frame(1, 0, 1, Keep, &[GREEN])]);
+        assert_eq!(gif_pixels(&bytes), vec![vec![RED, RED], vec![CLEAR, GREEN]]);
+    }
+
+    #[test]
+    fn gif_previous_disposal_restores_the_screen() {
+        use gif::DisposalMethod::*;
+        let frames = [
+            frame(0, 0, 2, Keep, &[RED, RED]),
+            frame(0, 0, 1, Previous, &[BLUE]),
+            frame(1, 0, 1, Keep, &[GREEN]),
+        ];
+        assert_eq!(
+            gif_pixels(&gif(2, 1, &frames)),
+            vec![vec![RED, RED], vec![BLUE, RED], vec![RED, GREEN]]
+        );
+    }
+
+    #[test]
+    fn gif_transparent_pixels_show_the_previous_frame() {
+        use gif::DisposalMethod::*;
+        let bytes = gif(2, 1, &[frame(0, 0, 2, Keep, &[RED, BLUE]), frame(0, 0, 2, Keep, &[CLEAR, GREEN])]);
+        assert_eq!(gif_pixels(&bytes), vec![vec![RED, BLUE], vec![RED, GREEN]]);
+    }
+
+    #[test]
+    fn bmp_16_bit_bitfields() {
+        // RGB 565: red then green, in one row padded to four bytes.
+        let data = [0x00, 0xF8, 0xE0, 0x07];
+        let image = decode(&bmp(2, 1, 16, 3, &[0xF800, 0x07E0, 0x001F], &data)).unwrap();
+        assert_eq!(rgb(&image), [[255, 0, 0], [0, 255, 0]]);
+    }
+
+    #[test]
+    fn bmp_alpha_bitfields() {
+        let masks = [0x0000_00FF, 0x0000_FF00, 0x00FF_0000, 0xFF00_0000];
+        let image = decode(&bmp(1, 1, 32, 6, &masks, &[10, 20, 30, 40])).unwrap();
+        assert_eq!(image.pixels, [[10, 20, 30, 40]]);
+    }
+
+    #[test]
+    fn bmp_row_order() {
+        // Two 24-bit rows of one pixel, each padded to four bytes: blue, then red.
+        let data = [255, 0, 0, 0, 0, 0, 255, 0];
+        let bottom_up = decode(&bmp(1, 2, 24, 0, &[], &data)).unwrap();
+        assert_eq!(rgb(&bottom_up), [[255, 0, 0], [0, 0, 255]]);
+        let top_down = decode(&bmp(1, -2, 24, 0, &[], &data)).unwrap();
+        assert_eq!(rgb(&top_down), [[0, 0, 255], [255, 0, 0]]);
+    }
+
+    #[test]
+    fn bmp_truncated() {
+        assert!(matches!(
+            decode(&bmp(2, 2, 24, 0, &[], &[0; 12])),
+            Err(LoadError::Decode(Format::Bmp, _))
+        ));
+        let huge = bmp(0x7FFF_FFFF, 0x7FFF_FFFF, 24, 0, &[], &[0; 6]);
+        assert!(matches!(decode(&huge), Err(LoadError::Decode(Format::Bmp, _))));
+    }
+
+    #[test]
+    fn pnm_ascii() {
+        // In bitmaps 1 is black.
+        let image = decode(b"P1\n# comment\n2 1\n1 0\n").unwrap();
+        assert_eq!(rgb(&image), [[0, 0, 0], [255, 255, 255]]);
+        let image = decode(b"P2 2 1 4\n0 4\n").unwrap();
+        assert_eq!(rgb(&image), [[0, 0, 0], [255, 255, 255]]);
+        let image = decode(b"P3 1 1 255\n10 20 30\n").unwrap();
+        assert_eq!(rgb(&image), [[10, 20, 30]]);
+    }
+
+    #[test]
+    fn pnm_binary() {
+        let image = decode(b"P4\n2 1\n\x80").unwrap();
+        assert_eq!(rgb(&image), [[0, 0, 0], [255, 255, 255]]);
+        let image = decode(b"P5 2 1 255\n\x00\x80").unwrap();
+        assert_eq!(rgb(&image), [[0, 0, 0], [128, 128, 128]]);
+        // Samples above 255 take two bytes, big-endian.
+        let image = decode(b"P5 1 1 65535\n\xFF\xFF").unwrap();
+        assert_eq!(rgb(&image), [[255, 255, 255]]);
+        let image = decode(b"P6 1 1 255\n\x01\x02\x03").unwrap();
+        assert_eq!(rgb(&image), [[1, 2, 3]]);
+    }
+
+    #[test]
+    fn pnm_truncated() {
+        for bytes in [
+            &b"P1 2 2\n1 0 1\n"[..],
+            b"P2 2 1 255\n0\n",
+            b"P3 1 1 255\n10 20\n",
+            b"P4 9 1\n\x00",
+            b"P5 1 1 65535\n\xFF",
+            b"P6 2 1 255\n\x01\x02\x03",
+            b"P6 99999999999 99999999999 255\n",
+        ] {
+            assert!(matches!(decode(bytes), Err(LoadError::Decode(Format::Pnm, _))));
+        }
+    }
+
+    #[test]
+    fn orient_undoes_every_orientation() {
+        // Stored as   a b c   with pixels numbered 0 to 5.
+        //             d e f
+        let mut image = Image::new(3, 2);
+        for i in 0..6 {
+            image.pixels[i] = [i as u8, 0, 0, 255];
+        }
+        let expected: [(usize, &[u8]); 8] = [
+            (3, &[0, 1, 2, 3, 4, 5]),
+            (3, &[2, 1, 0, 5, 4, 3]),
+            (3, &[5, 4, 3, 2, 1, 0]),
+            (3, &[3, 4, 5, 0, 1, 2]),
+            (2, &[0, 3, 1, 4, 2, 5]),
+            (2, &[3, 0, 4, 1, 5, 2]),
+            (2, &[5, 2, 4, 1, 3, 0]),
+            (2, &[2, 5, 1, 4, 0, 3]),
+        ];
+        for (orientation, (width, order)) in (1..=8).zip(expected) {
+            let oriented = image.orient(orientation);
+            let pixels = oriented.pixels.iter().map(|p| p[0]).collect::<Vec<_>>();
+            assert_eq!((oriented.width, &pixels[..]), (width, order), "orientation {}", orientation);
+        }
+    }
+
+    #[test]
+    fn orient_ignores_unknown_orientations() {
+        let mut image = Image::new(2, 1);
+        image.set(1, 0, [1, 2, 3, 4]);
+        let oriented = image.orient(9);
+        assert_eq!((oriented.width, oriented.height), (2, 1));
+        assert_eq!(oriented.pixels, image.pixels);
+    }
+
+    #[test]
+    fn jpeg_precision_reads_the_frame_header() {
+        // SOI, an APP0 segment, then SOF1 with 12-bit samples.
+        let bytes = [
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xC1, 0x00, 0x0B, 0x0C, 0x00, 0x01,
+        ];
+        assert_eq!(jpeg_precision(&bytes), Some(12));
+        // A scan before any frame header means there is none.
+        assert_eq!(jpeg_precision(&[0xFF, 0xD8, 0xFF, 0xDA, 0x00, 0x02]), None);
+    }
+
+    #[test]
+    fn jpeg_precision_of_an_8_bit_photo() {
+        let bytes = std::fs::read("assets/scene1.jpg").unwrap();
+        assert_eq!(jpeg_precision(&bytes), Some(8));
+    }
+}
@@ -0,0 +1,286 @@
+//! Dithering the colours of the sampled image before they are mapped to cells,
+//! so quantised gradients don't band.
+
+use crate::{Color, Image};
+
+/// How an error-diffusion ditherer spreads each pixel's error to the pixels
+/// not yet visited.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kernel {
+    /// The classic four-neighbour kernel.
+    FloydSteinberg,
+    /// Spreads only 6/8 of the error, for lighter, higher-contrast results.
+    Atkinson,
+    /// The three-row Sierra kernel.
+    Sierra,
+}
+
+impl Kernel {
+    // (dx, dy, weight) for each neighbour, and the sum the weights are out of.
+    fn weights(&self) -> (&'static [(isize, usize, f32)], f32) {
+        match self {
+            Kernel::FloydSteinberg => {
+                (&[(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)], 16.0)
+            }
+            Kernel::Atkinson => (
+                &[(1, 0, 1.0), (2, 0, 1.0), (-1, 1, 1.0), (0, 1, 1.0), (1, 1, 1.0), (0, 2, 1.0)],
+                8.0,
+            ),
+            Kernel::Sierra => (
+                &[
+                    (1, 0, 5.0),
+                    (2, 0, 3.0),
+                    (-2, 1, 2.0),
+                    (-1, 1, 4.0),
+                    (0, 1, 5.0),
+                    (1, 1, 4.0),
+                    (2, 1, 2.0),
+                    (-1, 2, 2.0),
+                    (0, 2, 3.0),
+                    (1, 2, 2.0),
+                ],
+                32.0,
+            ),
+        }
+    }
+}
+
+/// A tiled grid of thresholds (0.0 to 1.0) for ordered dithering.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThresholdMap {
+    // Never 0, so positions can wrap around the tile.
+    width: usize,
+    height: usize,
+    // `width * height` thresholds, row by row.
+    values: Vec<f32>,
+}
+
+impl ThresholdMap {
+    /// The largest Bayer matrix [`ThresholdMap::bayer`] builds.
+    pub const MAX_BAYER_SIZE: usize = 64;
+
+    /// The `size` x `size` Bayer matrix, with `size` rounded up to a power of
+    /// two and kept within 2 to [`ThresholdMap::MAX_BAYER_SIZE`].
+    pub fn bayer(size: usize) -> Self {
+        let size = size.clamp(2, Self::MAX_BAYER_SIZE).next_power_of_two();
+        // Each doubling places the previous matrix's pattern into four quadrants.
+        let mut matrix = vec![0usize];
+        let mut n = 1;
+        while n < size {
+            let mut next = vec![0; 4 * n * n];
+            for y in 0..n {
+                for x in 0..n {
+                    let v = 4 * matrix[y * n + x];
+                    next[y * 2 * n + x] = v;
+                    next[y * 2 * n + x + n] = v + 2;
+                    next[(y + n) * 2 * n + x] = v + 3;
+                    next[(y + n) * 2 * n + x + n] = v + 1;
+                }
+            }
+            matrix = next;
+            n *= 2;
+        }
+
+        let cells = (size * size) as f32;
+        Self {
+            width: size,
+            height: size,
+            values: matrix.into_iter().map(|v| (v as f32 + 0.5) / cells).collect(),
+        }
+    }
+
+    /// Uses an image's brightness as the thresholds, e.g. a blue-noise texture,
+    /// which hides the cross-hatching Bayer matrices are known for. An empty
+    /// image gives a map that leaves colours alone.
+    pub fn from_image(image: &Image) -> Self {
+        if image.pixels.is_empty() {
+            return Self {
+                width: 1,
+                height: 1,
+                values: vec![0.5],
+            };
+        }
+        let values = image
+            .pixels
+            .iter()
+            .map(|&[r, g, b, _]| (r as f32 + g as f32 + b as f32) / 3.0 / 256.0 + 1.0 / 512.0)
+            .collect();
+        Self {
+            width: image.width,
+            height: image.height,
+            values,
+        }
+    }
+
+    /// The threshold for position `x`, `y`, repeating the map as a tile.
+    pub fn at(&self, x: usize, y: usize) -> f32 {
+        self.values[(y % self.height) * self.width + x % self.width]
+    }
+}
+
+/// Whether and how to dither.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Dither {
+    /// Quantise each pixel on its own.
+    None,
+    /// Error diffusion, scanning every other row right to left when
+    /// `serpentine` to avoid diagonal streaks.
+    Diffusion {
+        /// Where the error goes.
+        kernel: Kernel,
+        /// Whether to alternate the scan direction.
+        serpentine: bool,
+    },
+    /// Ordered dithering, which depends only on position and so stays put
+    /// from one animation frame to the next.
+    Ordered(ThresholdMap),
+}
+
+impl Dither {
+    /// The ordered-dither threshold at `x`, `y`, 0.5 (no change) otherwise.
+    pub fn threshold(&self, x: usize, y: usize) -> f32 {
+        match self {
+            Dither::Ordered(map) => map.at(x, y),
+            _ => 0.5,
+        }
+    }
+}
+
+/// Dithers the `width`-wide grid of `colors` in place, leaving each one at a
+/// colour `quantise` returns, whose levels are `step` apart. Without
+/// dithering the colours are left alone.
+pub fn dither(
+    colors: &mut [Color],
+    width: usize,
+    dither: &Dither,
+    step: f32,
+    quantise: impl Fn(Color) -> Color,
+) {
+    match dither {
+        Dither::None => {}
+        Dither::Diffusion { kernel, serpentine } => {
+            diffuse(colors, width, *kernel, *serpentine, quantise)
+        }
+        Dither::Ordered(map) => {
+            for (i, color) in colors.iter_mut().enumerate() {
+                // Nudge by up to half a level either way before snapping.
+                let offset = ((map.at(i % width, i / width) - 0.5) * step).round() as i32;
+                *color = quantise(color.clamp(0, 255, offset));
+            }
+        }
+    }
+}
+
+fn diffuse(
+    colors: &mut [Color],
+    width: usize,
+    kernel: Kernel,
+    serpentine: bool,
+    quantise: impl Fn(Color) -> Color,
+) {
+    if width == 0 {
+        return;
+    }
+    let height = colors.len() / width;
+    let (weights, total) = kernel.weights();
+    // Pending values can leave 0..=255 until they are quantised.
+    let mut values = colors
+        .iter()
+        .map(|c| [c.red as f32, c.green as f32, c.blue as f32])
+        .collect::<Vec<_>>();
+
+    for y in 0..height {
+        let reverse = serpentine && y % 2 == 1;
+        for i in 0..width {
+            let x = if reverse { width - 1 - i } else { i };
+            let value = values[y * width + x];
+            let [r, g, b] = value.map(|v| v.round().clamp(0.0, 255.0) as u8);
+            let quantised = quantise(Color::new(r, g, b));
+            colors[y * width + x] = quantised;
+
+            let target = [quantised.red, quantised.green, quantised.blue];
+            let error = [0, 1, 2].map(|k| value[k] - target[k] as f32);
+            for &(dx, dy, weight) in weights {
+                // Mirror the kernel when scanning right to left.
+                let dx = if reverse { -dx } else { dx };
+                let (nx, ny) = (x as isize + dx, y + dy);
+                if nx < 0 || nx >= width as isize || ny >= height {
+                    continue;
+                }
+                let neighbour = &mut values[ny * width + nx as usize];
+                for k in 0..3 {
+                    neighbour[k] += error[k] * weight / total;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Diffuses a grid of greys where only (x, y) is lit, snapping just the
+    // lit pixel to black so the error it spreads is left untouched.
+    fn spread(width: usize, x: usize, y: usize, kernel: Kernel, serpentine: bool) -> Vec<u8> {
+        let mut colors = vec![Color::new(0, 0, 0); width * width];
+        colors[y * width + x] = Color::new(160, 160, 160);
+        let dither = Dither::Diffusion { kernel, serpentine };
+        let quantise = |c: Color| if c.red >= 100 { Color::new(0, 0, 0) } else { c };
+        super::dither(&mut colors, width, &dither, 0.0, quantise);
+        colors.iter().map(|c| c.red).collect()
+    }
+
+    #[test]
+    fn bayer_matrices() {
+        assert_eq!(ThresholdMap::bayer(2).values, [0.125, 0.625, 0.875, 0.375]);
+        let ranks = ThresholdMap::bayer(4).values.iter().map(|v| (v * 16.0 - 0.5) as u32).collect::<Vec<_>>();
+        assert_eq!(ranks, [0, 8, 2, 10, 12, 4, 14, 6, 3, 11, 1, 9, 15, 7, 13, 5]);
+        // Sizes round up to the next power of two.
+        assert_eq!(ThresholdMap::bayer(3), ThresholdMap::bayer(4));
+    }
+
+    #[test]
+    fn threshold_maps_wrap_around() {
+        let map = ThresholdMap::bayer(2);
+        assert_eq!(map.at(2, 0), map.at(0, 0));
+        assert_eq!(map.at(5, 7), map.at(1, 1));
+        assert_eq!(map.at(1, 0), 0.625);
+    }
+
+    #[test]
+    fn bayer_sizes_are_clamped() {
+        assert_eq!(ThresholdMap::bayer(0), ThresholdMap::bayer(2));
+        let largest = ThresholdMap::bayer(usize::MAX);
+        assert_eq!((largest.width, largest.height), (64, 64));
+    }
+
+    #[test]
+    fn empty_textures_leave_colours_alone() {
+        let map = ThresholdMap::from_image(&Image::new(0, 0));
+        assert_eq!(map.at(3, 7), 0.5);
+    }
+
+    #[test]
+    fn floyd_steinberg_spreads_7_3_5_1() {
+        let grid = spread(3, 1, 1, Kernel::FloydSteinberg, false);
+        assert_eq!(grid, [0, 0, 0, 0, 0, 70, 30, 50, 10]);
+    }
+
+    #[test]
+    fn serpentine_mirrors_odd_rows() {
+        // Row 1 is scanned right to left, so the kernel points the other way.
+        let grid = spread(3, 1, 1, Kernel::FloydSteinberg, true);
+        assert_eq!(grid, [0, 0, 0, 70, 0, 0, 10, 50, 30]);
+        // Even rows are scanned as usual.
+        let grid = spread(3, 1, 0, Kernel::FloydSteinberg, true);
+        assert_eq!(grid, [0, 0, 70, 30, 50, 10, 0, 0, 0]);
+    }
+
+    #[test]
+    fn atkinson_drops_a_quarter_of_the_error() {
+        let grid = spread(4, 1, 0, Kernel::Atkinson, false);
+        assert_eq!(grid, [0, 0, 20, 20, 20, 20, 20, 0, 0, 20, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(grid.iter().map(|&v| v as u32).sum::<u32>(), 120);
+    }
+}
@@ -0,0 +1,140 @@
+//! Walking the segments of a JPEG, and just enough EXIF parsing to find its
+//! Orientation tag.
+
+const ORIENTATION_TAG: u16 = 0x0112;
+
+/// The EXIF Orientation (1-8) of a JPEG file, if it has one.
+pub fn orientation(bytes: &[u8]) -> Option<u16> {
+    segments(bytes)
+        .find(|(marker, segment)| *marker == 0xE1 && segment.starts_with(b"Exif\0\0"))
+        .and_then(|(_, segment)| tiff_orientation(&segment[6..]))
+}
+
+/// The marker and payload of each JPEG segment up to the start of the scan.
+/// Nothing for data that isn't a JPEG; the last payload is cut short when the
+/// file is.
+pub(crate) fn segments(bytes: &[u8]) -> impl Iterator<Item = (u8, &[u8])> + '_ {
+    let mut i = if bytes.starts_with(&[0xFF, 0xD8]) { 2 } else { bytes.len() };
+    std::iter::from_fn(move || {
+        while i + 4 <= bytes.len() && bytes[i] == 0xFF {
+            let marker = bytes[i + 1];
+            // Fill bytes may pad markers.
+            if marker == 0xFF {
+                i += 1;
+                continue;
+            }
+            // Start of scan: the metadata segments are over.
+            if marker == 0xDA || marker == 0xD9 {
+                break;
+            }
+            let length = u16::from_be_bytes([bytes[i + 2], bytes[i + 3]]) as usize;
+            if length < 2 {
+                break;
+            }
+            let start = i + 4;
+            i += 2 + length;
+            return Some((marker, &bytes[start..i.min(bytes.len())]));
+        }
+        i = bytes.len();
+        None
+    })
+}
+
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let little_endian = match tiff.get(..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| {
+        let b = tiff.get(offset..offset + 2)?;
+        Some(if little_endian {
+            u16::from_le_bytes([b[0], b[1]])
+        } else {
+            u16::from_be_bytes([b[0], b[1]])
+        })
+    };
+    let u32_at = |offset: usize| {
+        let b = tiff.get(offset..offset + 4)?;
+        Some(if little_endian {
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+        } else {
+            u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+        })
+    };
+
+    if u16_at(2)? != 42 {
+        return None;
+    }
+
+    let ifd = u32_at(4)? as usize;
+    let entries = u16_at(ifd)? as usize;
+    for n in 0..entries {
+        let entry = ifd + 2 + n * 12;
+        if u16_at(entry)? == ORIENTATION_TAG {
+            let value = u16_at(entry + 8)?;
+            return (1..=8).contains(&value).then_some(value);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A JPEG holding just an APP1 segment with one IFD entry, then the scan.
+    fn jpeg(tiff_header: &[u8], entry: &[u8]) -> Vec<u8> {
+        let mut tiff = tiff_header.to_vec();
+        tiff.extend(entry);
+        tiff.extend([0; 4]);
+        let length = (2 + 6 + tiff.len()) as u16;
+        let mut bytes = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        bytes.extend(length.to_be_bytes());
+        bytes.extend(b"Exif\0\0");
+        bytes.extend(tiff);
+        bytes.extend([0xFF, 0xDA, 0x00, 0x02]);
+        bytes
+    }
+
+    #[test]
+    fn little_endian_orientation() {
+        let header = b"II\x2A\x00\x08\x00\x00\x00\x01\x00";
+        let entry = [0x12, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00];
+        assert_eq!(orientation(&jpeg(header, &entry)), Some(6));
+    }
+
+    #[test]
+    fn big_endian_orientation() {
+        let header = b"MM\x00\x2A\x00\x00\x00\x08\x00\x01";
+        let entry = [0x01, 0x12, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x08, 0x00, 0x00];
+        assert_eq!(orientation(&jpeg(header, &entry)), Some(8));
+    }
+
+    #[test]
+    fn missing_or_invalid_orientation() {
+        let header = b"II\x2A\x00\x08\x00\x00\x00\x01\x00";
+        // Another tag, and an orientation out of range.
+        let other = [0x0F, 0x01, 0x02, 0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00];
+        assert_eq!(orientation(&jpeg(header, &other)), None);
+        let invalid = [0x12, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00];
+        assert_eq!(orientation(&jpeg(header, &invalid)), None);
+        // Not a JPEG, and a JPEG without EXIF.
+        assert_eq!(orientation(b"\x89PNG\r\n\x1a\n"), None);
+        assert_eq!(orientation(&[0xFF, 0xD8, 0xFF, 0xDA, 0x00, 0x02]), None);
+    }
+
+    #[test]
+    fn segments_stop_at_the_scan() {
+        // Fill bytes before APP0, then the scan, so the SOF0 after it is never reached.
+        let bytes = [
+            0xFF, 0xD8, 0xFF, 0xFF, 0xE0, 0x00, 0x03, 0x01, 0xFF, 0xDA, 0x00, 0x02, 0xFF, 0xC0, 0x00, 0x02,
+        ];
+        let found: Vec<_> = segments(&bytes).collect();
+        assert_eq!(found, vec![(0xE0, &[0x01][..])]);
+        let truncated = [0xFF, 0xD8, 0xFF, 0xFE, 0x00, 0x08, 0x41, 0x42];
+        assert_eq!(segments(&truncated).collect::<Vec<_>>(), vec![(0xFE, &b"AB"[..])]);
+        assert_eq!(segments(b"\x89PNG\r\n\x1a\n").count(), 0);
+    }
+}
@@ -0,0 +1,464 @@
+//! Bitmap fonts (BDF and PSF) for measuring how much ink each glyph puts on
+//! screen.
+
+use std::{collections::HashMap, fmt, fs, io};
+
+// The largest cell accepted along either axis, far beyond any terminal font.
+const MAX_CELL: usize = 256;
+
+/// Why a font could not be loaded.
+#[derive(Debug)]
+pub enum FontError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The header or a glyph did not parse.
+    Malformed(String),
+    /// The data is neither BDF nor PSF.
+    UnsupportedFormat,
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FontError::Io(err) => write!(f, "could not read font: {}", err),
+            FontError::Malformed(message) => write!(f, "could not parse font: {}", message),
+            FontError::UnsupportedFormat => write!(f, "unsupported font format (expected BDF or PSF)"),
+        }
+    }
+}
+
+impl std::error::Error for FontError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FontError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FontError {
+    fn from(err: io::Error) -> Self {
+        FontError::Io(err)
+    }
+}
+
+fn malformed(message: &str) -> FontError {
+    FontError::Malformed(message.to_string())
+}
+
+/// One glyph rendered into a full character cell, row-major, `true` for ink.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Glyph {
+    /// Width in pixels, the same as the font's.
+    pub width: usize,
+    /// Height in pixels, the same as the font's.
+    pub height: usize,
+    /// `width * height` pixels, top row first.
+    pub pixels: Vec<bool>,
+}
+
+impl Glyph {
+    /// The fraction of the cell covered by ink, 0.0 to 1.0.
+    pub fn coverage(&self) -> f32 {
+        let ink = self.pixels.iter().filter(|&&p| p).count();
+        ink as f32 / self.pixels.len().max(1) as f32
+    }
+}
+
+/// A fixed-size bitmap font, with every glyph the size of one cell.
+#[derive(Clone, Debug)]
+pub struct BitmapFont {
+    /// Cell width in pixels.
+    pub width: usize,
+    /// Cell height in pixels.
+    pub height: usize,
+    glyphs: HashMap<char, Glyph>,
+}
+
+impl BitmapFont {
+    /// Reads a BDF or PSF (version 1 or 2) font.
+    pub fn open(path: &str) -> Result<Self, FontError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    /// Parses a BDF or PSF font held in memory, detected from its header.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FontError> {
+        if bytes.starts_with(&[0x36, 0x04]) {
+            parse_psf1(bytes)
+        } else if bytes.starts_with(&[0x72, 0xB5, 0x4A, 0x86]) {
+            parse_psf2(bytes)
+        } else if bytes.starts_with(b"STARTFONT") {
+            let text = std::str::from_utf8(bytes).map_err(|_| malformed("BDF file is not UTF-8"))?;
+            parse_bdf(text)
+        } else {
+            Err(FontError::UnsupportedFormat)
+        }
+    }
+
+    /// The glyph for `char`, if the font has one.
+    pub fn glyph(&self, char: char) -> Option<&Glyph> {
+        self.glyphs.get(&char)
+    }
+
+    /// Sorts `chars` from the least to the most ink, ready to be used as a
+    /// ramp. Characters the font has no glyph for are returned separately.
+    pub fn sort_by_coverage(&self, chars: &[char]) -> (Vec<char>, Vec<char>) {
+        let (mut found, missing): (Vec<char>, Vec<char>) =
+            chars.iter().partition(|c| self.glyphs.contains_key(c));
+        found.sort_by(|a, b| self.glyphs[a].coverage().total_cmp(&self.glyphs[b].coverage()));
+        (found, missing)
+    }
+}
+
+fn parse_bdf(text: &str) -> Result<BitmapFont, FontError> {
+    let numbers = |fields: &[&str]| -> Result<Vec<i32>, FontError> {
+        fields
+            .iter()
+            .map(|f| f.parse().map_err(|_| malformed("bad number in BDF file")))
+            .collect()
+    };
+
+    let mut lines = text.lines().map(str::trim);
+    // The font bounding box is the cell every glyph is placed in.
+    let mut cell = None;
+    let mut glyphs = HashMap::new();
+
+    while let Some(line) = lines.next() {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        match fields.first() {
+            Some(&"FONTBOUNDINGBOX") if fields.len() == 5 => {
+                let bounds = numbers(&fields[1..])?;
+                let size = 1..=MAX_CELL as i32;
+                if !size.contains(&bounds[0]) || !size.contains(&bounds[1]) {
+                    return Err(malformed("FONTBOUNDINGBOX size out of range"));
+                }
+                cell = Some(bounds);
+            }
+            Some(&"STARTCHAR") => {
+                let [width, height, x_offset, y_offset] = cell
+                    .as_deref()
+                    .and_then(|c| c.try_into().ok())
+                    .ok_or_else(|| malformed("glyph before FONTBOUNDINGBOX"))?;
+                let mut encoding = None;
+                let mut bbx = [width, height, x_offset, y_offset];
+                let mut pixels = vec![false; (width * height) as usize];
+
+                for line in lines.by_ref() {
+                    let fields = line.split_whitespace().collect::<Vec<_>>();
+                    match fields.first() {
+                        Some(&"ENCODING") if fields.len() >= 2 => {
+                            encoding = numbers(&fields[1..2])?.first().copied();
+                        }
+                        Some(&"BBX") if fields.len() == 5 => {
+                            bbx = numbers(&fields[1..])?
+                                .try_into()
+                                .map_err(|_| malformed("bad BBX line"))?;
+                        }
+                        Some(&"BITMAP") => break,
+                        _ => {}
+                    }
+                }
+
+                // Place the glyph's own box inside the cell, both measured
+                // from the baseline.
+                let left = bbx[2].checked_sub(x_offset);
+                let top = height
+                    .checked_add(y_offset)
+                    .zip(bbx[1].checked_add(bbx[3]))
+                    .and_then(|(cell_top, glyph_top)| cell_top.checked_sub(glyph_top));
+                let (Some(left), Some(top)) = (left, top) else {
+                    return Err(malformed("BBX offsets out of range"));
+                };
+                for (row, line) in lines.by_ref().enumerate() {
+                    if line == "ENDCHAR" {
+                        break;
+                    }
+                    let digits = line
+                        .chars()
+                        .map(|c| c.to_digit(16).ok_or_else(|| malformed("bad BITMAP row")))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    for x in 0..bbx[0].min(digits.len() as i32 * 4) {
+                        let ink = digits[x as usize / 4] >> (3 - x % 4) & 1 == 1;
+                        let cx = left.checked_add(x);
+                        let cy = i32::try_from(row).ok().and_then(|row| top.checked_add(row));
+                        if let (true, Some(cx), Some(cy)) = (ink, cx, cy) {
+                            if (0..width).contains(&cx) && (0..height).contains(&cy) {
+                                pixels[(cy * width + cx) as usize] = true;
+                            }
+                        }
+                    }
+                }
+
+                // Negative encodings are glyphs without a code point.
+                if let Some(char) = encoding.and_then(|e| u32::try_from(e).ok()).and_then(char::from_u32) {
+                    let glyph = Glyph {
+                        width: width as usize,
+                        height: height as usize,
+                        pixels,
+                    };
+                    glyphs.insert(char, glyph);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let cell = cell.ok_or_else(|| malformed("missing FONTBOUNDINGBOX"))?;
+    Ok(BitmapFont {
+        width: cell[0] as usize,
+        height: cell[1] as usize,
+        glyphs,
+    })
+}
+
+fn parse_psf1(bytes: &[u8]) -> Result<BitmapFont, FontError> {
+    let mode = *bytes.get(2).ok_or_else(|| malformed("truncated PSF header"))?;
+    let height = *bytes.get(3).ok_or_else(|| malformed("truncated PSF header"))? as usize;
+    let count = if mode & 0x01 != 0 { 512 } else { 256 };
+    if height == 0 {
+        return Err(malformed("PSF glyph size out of range"));
+    }
+    let glyphs = bytes
+        .get(4..4 + count * height)
+        .ok_or_else(|| malformed("truncated PSF glyphs"))?;
+
+    // Without a table, glyph n shows code point n.
+    let table = if mode & 0x02 != 0 {
+        let entries = bytes[4 + count * height..]
+            .chunks_exact(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]) as u32);
+        Some(psf_table(entries, 0xFFFF, 0xFFFE))
+    } else {
+        None
+    };
+
+    Ok(psf_font(glyphs, count, 8, height, table))
+}
+
+fn parse_psf2(bytes: &[u8]) -> Result<BitmapFont, FontError> {
+    let field = |i: usize| {
+        bytes
+            .get(4 + i * 4..8 + i * 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            .ok_or_else(|| malformed("truncated PSF header"))
+    };
+    let (header, flags, count, size, height, width) =
+        (field(1)?, field(2)?, field(3)?, field(4)?, field(5)?, field(6)?);
+    if !(1..=MAX_CELL).contains(&width) || !(1..=MAX_CELL).contains(&height) {
+        return Err(malformed("PSF glyph size out of range"));
+    }
+    if size != height * width.div_ceil(8) {
+        return Err(malformed("PSF glyph size does not match its dimensions"));
+    }
+    let end = count.checked_mul(size).and_then(|n| n.checked_add(header));
+    let glyphs = end
+        .and_then(|end| bytes.get(header..end))
+        .ok_or_else(|| malformed("truncated PSF glyphs"))?;
+
+    let table = if flags & 0x01 != 0 {
+        let text = &bytes[header + glyphs.len()..];
+        Some(psf2_table(text))
+    } else {
+        None
+    };
+
+    Ok(psf_font(glyphs, count, width, height, table))
+}
+
+// Splits a PSF1 Unicode table: each glyph lists its code points, then maybe
+// multi-character sequences after `sequence`, and ends with `end`.
+fn psf_table(entries: impl Iterator<Item = u32>, end: u32, sequence: u32) -> Vec<Vec<char>> {
+    let mut table = vec![vec![]];
+    let mut in_sequence = false;
+    for entry in entries {
+        if entry == end {
+            table.push(vec![]);
+            in_sequence = false;
+        } else if entry == sequence {
+            in_sequence = true;
+        } else if !in_sequence {
+            if let Some(char) = char::from_u32(entry) {
+                table.last_mut().unwrap().push(char);
+            }
+        }
+    }
+    table
+}
+
+// PSF2 tables hold UTF-8 with 0xFF ending each glyph and 0xFE before sequences.
+fn psf2_table(bytes: &[u8]) -> Vec<Vec<char>> {
+    bytes
+        .split(|&b| b == 0xFF)
+        .map(|entry| {
+            let singles = entry.split(|&b| b == 0xFE).next().unwrap_or(&[]);
+            String::from_utf8_lossy(singles).chars().filter(|&c| c != '\u{FFFD}').collect()
+        })
+        .collect()
+}
+
+fn psf_font(
+    data: &[u8],
+    count: usize,
+    width: usize,
+    height: usize,
+    table: Option<Vec<Vec<char>>>,
+) -> BitmapFont {
+    let row_bytes = width.div_ceil(8);
+    let size = row_bytes * height;
+    let mut glyphs = HashMap::new();
+
+    for index in 0..count {
+        let bitmap = &data[index * size..(index + 1) * size];
+        let pixels = (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                bitmap[y * row_bytes + x / 8] >> (7 - x % 8) & 1 == 1
+            })
+            .collect::<Vec<_>>();
+        let chars = match &table {
+            Some(table) => table.get(index).cloned().unwrap_or_default(),
+            None => char::from_u32(index as u32).into_iter().collect(),
+        };
+        for char in chars {
+            let glyph = Glyph {
+                width,
+                height,
+                pixels: pixels.clone(),
+            };
+            glyphs.entry(char).or_insert(glyph);
+        }
+    }
+
+    BitmapFont {
+        width,
+        height,
+        glyphs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The positions of a glyph's ink, as (x, y).
+    fn ink(glyph: &Glyph) -> Vec<(usize, usize)> {
+        (0..glyph.pixels.len())
+            .filter(|&i| glyph.pixels[i])
+            .map(|i| (i % glyph.width, i / glyph.width))
+            .collect()
+    }
+
+    #[test]
+    fn bdf_glyphs_are_placed_in_the_cell() {
+        // A 4x6 cell with the baseline one row above the bottom, and a 2x2
+        // glyph one pixel in, sitting on the baseline.
+        let bdf = "STARTFONT 2.1
+FONTBOUNDINGBOX 4 6 0 -1
+CHARS 2
+STARTCHAR A
+ENCODING 65
+BBX 2 2 1 0
+BITMAP
+C0
+40
+ENDCHAR
+STARTCHAR unnamed
+ENCODING -1
+BBX 4 6 0 -1
+BITMAP
+F0
+F0
+F0
+F0
+F0
+F0
+ENDCHAR
+ENDFONT
+";
+        let font = BitmapFont::from_bytes(bdf.as_bytes()).unwrap();
+        assert_eq!((font.width, font.height), (4, 6));
+        let glyph = font.glyph('A').unwrap();
+        assert_eq!((glyph.width, glyph.height), (4, 6));
+        assert_eq!(ink(glyph), [(1, 3), (2, 3), (2, 4)]);
+        assert_eq!(font.glyphs.len(), 1);
+    }
+
+    #[test]
+    fn psf1_glyphs_by_index() {
+        let mut psf = vec![0x36, 0x04, 0x00, 2];
+        psf.extend(vec![0; 256 * 2]);
+        psf[4 + 65 * 2] = 0b1000_0000;
+        psf[4 + 65 * 2 + 1] = 0b0000_0001;
+        let font = BitmapFont::from_bytes(&psf).unwrap();
+        assert_eq!((font.width, font.height), (8, 2));
+        assert_eq!(ink(font.glyph('A').unwrap()), [(0, 0), (7, 1)]);
+    }
+
+    #[test]
+    fn psf1_unicode_table() {
+        let mut psf = vec![0x36, 0x04, 0x02, 1];
+        psf.extend(vec![0; 256]);
+        psf[4] = 0xFF;
+        // Glyph 0 shows é and (after a sequence marker) nothing else; the
+        // rest have no code points.
+        psf.extend([0xE9, 0x00, 0xFE, 0xFF, 0x65, 0x00, 0x01, 0x03, 0xFF, 0xFF]);
+        for _ in 1..256 {
+            psf.extend([0xFF, 0xFF]);
+        }
+        let font = BitmapFont::from_bytes(&psf).unwrap();
+        assert_eq!(ink(font.glyph('é').unwrap()).len(), 8);
+        assert!(font.glyph('e').is_none());
+        assert!(font.glyph('\0').is_none());
+    }
+
+    #[test]
+    fn psf2_wide_glyphs_and_table() {
+        let field = |v: u32| v.to_le_bytes();
+        let mut psf = vec![0x72, 0xB5, 0x4A, 0x86];
+        // Version, header size, flags (has table), glyph count, glyph size,
+        // height and width.
+        for v in [0, 32, 1, 1, 4, 2, 10] {
+            psf.extend(field(v));
+        }
+        // Rows are padded to whole bytes: ink at x = 0 and x = 9.
+        psf.extend([0b1000_0000, 0b0100_0000, 0, 0]);
+        psf.extend("Ж".as_bytes());
+        psf.push(0xFF);
+        let font = BitmapFont::from_bytes(&psf).unwrap();
+        assert_eq!((font.width, font.height), (10, 2));
+        assert_eq!(ink(font.glyph('Ж').unwrap()), [(0, 0), (9, 0)]);
+    }
+
+    #[test]
+    fn malformed_fonts() {
+        assert!(matches!(BitmapFont::from_bytes(b"hello"), Err(FontError::UnsupportedFormat)));
+        assert!(matches!(
+            BitmapFont::from_bytes(&[0x36, 0x04, 0x00, 8, 0]),
+            Err(FontError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn oversized_fonts() {
+        let malformed = |bytes: &[u8]| matches!(BitmapFont::from_bytes(bytes), Err(FontError::Malformed(_)));
+        assert!(malformed(b"STARTFONT 2.1\nFONTBOUNDINGBOX 100000 100000 0 0\nCHARS 0\n"));
+        assert!(malformed(b"STARTFONT 2.1\nFONTBOUNDINGBOX 0 8 0 0\nCHARS 0\n"));
+        // Offsets that overflow when placing the glyph in the cell.
+        let bdf = "STARTFONT 2.1\nFONTBOUNDINGBOX 4 6 0 2147483647\nCHARS 1\nSTARTCHAR A\nENCODING 65\n\
+                   BBX 1 1 0 -2147483648\nBITMAP\n80\nENDCHAR\n";
+        assert!(malformed(bdf.as_bytes()));
+
+        let psf2 = |count: u32, size: u32, height: u32, width: u32| {
+            let mut psf = vec![0x72, 0xB5, 0x4A, 0x86];
+            for v in [0, 32, 0, count, size, height, width] {
+                psf.extend(v.to_le_bytes());
+            }
+            psf
+        };
+        assert!(malformed(&psf2(1, 0, 0, 8)));
+        assert!(malformed(&psf2(u32::MAX, 32, 32, 8)));
+        assert!(malformed(&psf2(1, 1 << 20, 1 << 20, 8)));
+        // PSF1 with no rows per glyph.
+        assert!(malformed(&[0x36, 0x04, 0x00, 0]));
+    }
+}
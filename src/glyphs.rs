@@ -0,0 +1,191 @@
+//! Glyph shapes for matching blocks of pixels against whole characters.
+
+use std::sync::OnceLock;
+
+use crate::{BitmapFont, Cell, Color, ColorSpace};
+
+/// Pixels across each glyph shape, and so across each cell's block.
+pub const SHAPE_WIDTH: usize = 4;
+/// Pixels down each glyph shape.
+pub const SHAPE_HEIGHT: usize = 8;
+
+// Hand-drawn shapes for when no font is given, `#` for ink.
+const BUILTIN: &[(char, [&str; SHAPE_HEIGHT])] = &[
+    (' ', ["....", "....", "....", "....", "....", "....", "....", "...."]),
+    ('.', ["....", "....", "....", "....", "....", "....", ".#..", "...."]),
+    (',', ["....", "....", "....", "....", "....", ".#..", ".#..", "#..."]),
+    ('\'', [".#..", ".#..", "....", "....", "....", "....", "....", "...."]),
+    ('`', ["#...", ".#..", "....", "....", "....", "....", "....", "...."]),
+    ('"', ["#.#.", "#.#.", "....", "....", "....", "....", "....", "...."]),
+    ('-', ["....", "....", "....", "####", "....", "....", "....", "...."]),
+    ('_', ["....", "....", "....", "....", "....", "....", "....", "####"]),
+    ('=', ["....", "....", "####", "....", "####", "....", "....", "...."]),
+    ('~', ["....", "....", ".#.#", "#.#.", "....", "....", "....", "...."]),
+    (':', ["....", "....", ".#..", "....", "....", ".#..", "....", "...."]),
+    (';', ["....", "....", ".#..", "....", "....", ".#..", "#...", "...."]),
+    ('|', [".#..", ".#..", ".#..", ".#..", ".#..", ".#..", ".#..", ".#.."]),
+    ('!', [".#..", ".#..", ".#..", ".#..", ".#..", "....", ".#..", "...."]),
+    ('i', ["....", ".#..", "....", ".#..", ".#..", ".#..", ".#..", "...."]),
+    ('l', ["##..", ".#..", ".#..", ".#..", ".#..", ".#..", ".#..", "...."]),
+    ('/', ["...#", "...#", "..#.", "..#.", ".#..", ".#..", "#...", "#..."]),
+    ('\\', ["#...", "#...", ".#..", ".#..", "..#.", "..#.", "...#", "...#"]),
+    ('(', ["..#.", ".#..", "#...", "#...", "#...", "#...", ".#..", "..#."]),
+    (')', [".#..", "..#.", "...#", "...#", "...#", "...#", "..#.", ".#.."]),
+    ('[', ["##..", "#...", "#...", "#...", "#...", "#...", "#...", "##.."]),
+    (']', ["..##", "...#", "...#", "...#", "...#", "...#", "...#", "..##"]),
+    ('<', ["....", "..#.", ".#..", "#...", ".#..", "..#.", "....", "...."]),
+    ('>', ["....", ".#..", "..#.", "...#", "..#.", ".#..", "....", "...."]),
+    ('^', [".#..", "#.#.", "....", "....", "....", "....", "....", "...."]),
+    ('v', ["....", "....", "....", "#.#.", "#.#.", "#.#.", ".#..", "...."]),
+    ('o', ["....", "....", "....", ".##.", "#..#", "#..#", ".##.", "...."]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", "#..#", ".##.", "...."]),
+    ('x', ["....", "....", "....", "#..#", ".##.", ".##.", "#..#", "...."]),
+    ('+', ["....", "....", ".#..", "###.", ".#..", "....", "....", "...."]),
+    ('*', ["....", "#.#.", ".#..", "###.", ".#..", "#.#.", "....", "...."]),
+    ('#', ["....", ".#.#", "####", ".#.#", "####", ".#.#", "....", "...."]),
+    ('@', [".##.", "#..#", "#.##", "#.##", "#.##", "#...", ".##.", "...."]),
+    ('T', ["####", ".#..", ".#..", ".#..", ".#..", ".#..", ".#..", "...."]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "#...", "####", "...."]),
+    ('J', ["...#", "...#", "...#", "...#", "...#", "#..#", ".##.", "...."]),
+    ('7', ["####", "...#", "..#.", "..#.", ".#..", ".#..", ".#..", "...."]),
+    ('Y', ["#..#", "#..#", ".##.", ".#..", ".#..", ".#..", ".#..", "...."]),
+    ('V', ["#..#", "#..#", "#..#", "#..#", ".##.", ".##.", ".#..", "...."]),
+    ('█', ["####", "####", "####", "####", "####", "####", "####", "####"]),
+    ('▀', ["####", "####", "####", "####", "....", "....", "....", "...."]),
+    ('▄', ["....", "....", "....", "....", "####", "####", "####", "####"]),
+    ('▌', ["##..", "##..", "##..", "##..", "##..", "##..", "##..", "##.."]),
+    ('▐', ["..##", "..##", "..##", "..##", "..##", "..##", "..##", "..##"]),
+];
+
+// A glyph's ink per pixel (0.0 to 1.0) with the sums its colour fit needs.
+#[derive(Clone, Debug)]
+struct Shape {
+    char: char,
+    ink: Vec<f32>,
+    sum: f32,
+    squares: f32,
+    det: f32,
+}
+
+impl Shape {
+    fn new(char: char, ink: Vec<f32>) -> Self {
+        let sum = ink.iter().sum::<f32>();
+        let squares = ink.iter().map(|p| p * p).sum::<f32>();
+        let det = ink.len() as f32 * squares - sum * sum;
+        Self {
+            char,
+            ink,
+            sum,
+            squares,
+            det,
+        }
+    }
+}
+
+/// The glyphs [`GlyphSet::cell`] chooses from, as `SHAPE_WIDTH` x
+/// `SHAPE_HEIGHT` ink patterns.
+#[derive(Clone, Debug)]
+pub struct GlyphSet {
+    shapes: Vec<Shape>,
+}
+
+impl GlyphSet {
+    /// The hand-drawn ASCII and block glyphs.
+    pub fn builtin() -> &'static GlyphSet {
+        static BUILTIN_SET: OnceLock<GlyphSet> = OnceLock::new();
+        BUILTIN_SET.get_or_init(|| {
+            let shapes = BUILTIN
+                .iter()
+                .map(|(char, rows)| {
+                    let ink = rows
+                        .iter()
+                        .flat_map(|row| row.chars().map(|c| if c == '#' { 1.0 } else { 0.0 }))
+                        .collect();
+                    Shape::new(*char, ink)
+                })
+                .collect();
+            GlyphSet { shapes }
+        })
+    }
+
+    /// Shapes of `chars` scaled down from a bitmap font, skipping the ones
+    /// the font has no glyph for.
+    pub fn from_font(font: &BitmapFont, chars: &[char]) -> Self {
+        let shapes = chars
+            .iter()
+            .filter_map(|&char| {
+                let glyph = font.glyph(char)?;
+                let mut ink = vec![0.0; SHAPE_WIDTH * SHAPE_HEIGHT];
+                let mut count = vec![0.0; SHAPE_WIDTH * SHAPE_HEIGHT];
+                for (i, &pixel) in glyph.pixels.iter().enumerate() {
+                    let (x, y) = (i % glyph.width, i / glyph.width);
+                    let target = y * SHAPE_HEIGHT / glyph.height * SHAPE_WIDTH + x * SHAPE_WIDTH / glyph.width;
+                    ink[target] += pixel as u8 as f32;
+                    count[target] += 1.0;
+                }
+                for (ink, count) in ink.iter_mut().zip(count) {
+                    *ink /= f32::max(count, 1.0);
+                }
+                Some(Shape::new(char, ink))
+            })
+            .collect();
+        Self { shapes }
+    }
+
+    /// Whether the set has no shapes to match against.
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    /// Picks the glyph and colours that best reproduce a `SHAPE_WIDTH` x
+    /// `SHAPE_HEIGHT` block of pixels, in row-major order.
+    ///
+    /// Each glyph is drawn as `bg + ink * (fg - bg)`, so its best colours
+    /// follow from a least-squares line fit per channel, and the glyph with the
+    /// smallest remaining error wins. The fit is made in `space`.
+    pub fn cell(&self, pixels: &[Color], space: ColorSpace) -> Cell {
+        let channels = pixels.iter().map(|c| c.channels(space)).collect::<Vec<_>>();
+        let n = pixels.len() as f32;
+        let mut sums = [0.0f32; 3];
+        let mut squares = 0.0;
+        for c in channels.iter() {
+            for k in 0..3 {
+                sums[k] += c[k];
+                squares += c[k] * c[k];
+            }
+        }
+
+        let average = Color::average(pixels, space);
+        let mut best = Cell::new(' ', average, average);
+        let mut best_error = f32::MAX;
+
+        for shape in self.shapes.iter() {
+            let mut weighted = [0.0f32; 3];
+            for (c, ink) in channels.iter().zip(shape.ink.iter()) {
+                for k in 0..3 {
+                    weighted[k] += c[k] * ink;
+                }
+            }
+
+            // An even pattern (no ink, or all ink) can only show one colour.
+            let (bg, delta) = if shape.det.abs() < 1e-3 {
+                (sums.map(|s| s / n), [0.0; 3])
+            } else {
+                let bg = [0, 1, 2].map(|k| (shape.squares * sums[k] - shape.sum * weighted[k]) / shape.det);
+                let delta = [0, 1, 2].map(|k| (n * weighted[k] - shape.sum * sums[k]) / shape.det);
+                (bg, delta)
+            };
+            // What the fit leaves unexplained: Σc² minus the explained part.
+            let explained = (0..3).map(|k| bg[k] * sums[k] + delta[k] * weighted[k]).sum::<f32>();
+            let error = squares - explained;
+
+            if error < best_error - 1e-3 {
+                best_error = error;
+                let fg = [0, 1, 2].map(|k| bg[k] + delta[k]);
+                best = Cell::new(shape.char, Color::from_channels(fg, space), Color::from_channels(bg, space));
+            }
+        }
+
+        best
+    }
+}
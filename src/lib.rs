@@ -0,0 +1,35 @@
+//! Renders images as coloured text: decoding, resampling, mapping colours to
+//! glyphs, and drawing the result to a terminal or a file.
+
+#![warn(missing_docs)]
+
+pub mod canvas;
+pub mod cell;
+pub mod color;
+pub mod decode;
+pub mod dither;
+pub mod exif;
+pub mod font;
+pub mod glyphs;
+pub mod load;
+pub mod output;
+pub mod palette;
+pub mod resample;
+pub mod shapes;
+pub mod term;
+
+pub use canvas::{Canvas, Renderable};
+pub use cell::{Cell, CellMode, DEFAULT_RAMP};
+pub use color::{Color, ColorMetric, ColorSpace};
+pub use decode::{Format, Image, LoadError};
+pub use dither::{Dither, Kernel, ThresholdMap};
+pub use font::{BitmapFont, FontError, Glyph};
+pub use glyphs::GlyphSet;
+pub use load::{
+    decode_image, gif_frames_from_bytes, image_to_canvas, load_gif_frames, load_image_as_canvas,
+    play_in_terminal, Anchor, FitMode, LoadOptions,
+};
+pub use output::{ColorDepth, ColorMode, OutputFormat};
+pub use palette::{Palette, PaletteError};
+pub use resample::Filter;
+pub use shapes::{Circle, DialogBox, Rectangle, Vector2};
@@ -0,0 +1,405 @@
+//! Turning image files into canvases.
+
+use std::{
+    fs,
+    io::Read,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    cell,
+    decode::{self, Image, LoadError},
+    dither::{self, Dither},
+    exif,
+    resample::{self, Filter},
+    term::{self, ResizeWatcher},
+    Canvas, Cell, CellMode, Color, ColorDepth, ColorMetric, ColorSpace, GlyphSet, Palette,
+    DEFAULT_RAMP,
+};
+
+/// Which part of the image [`FitMode::Cover`] keeps.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Anchor {
+    /// The top-left corner.
+    TopLeft,
+    /// The middle of the top edge.
+    Top,
+    /// The top-right corner.
+    TopRight,
+    /// The middle of the left edge.
+    Left,
+    /// The centre.
+    Center,
+    /// The middle of the right edge.
+    Right,
+    /// The bottom-left corner.
+    BottomLeft,
+    /// The middle of the bottom edge.
+    Bottom,
+    /// The bottom-right corner.
+    BottomRight,
+}
+
+impl Anchor {
+    /// How far along each axis the kept region sits, from 0.0 (start) to 1.0 (end).
+    pub fn offsets(&self) -> (f32, f32) {
+        match self {
+            Anchor::TopLeft => (0.0, 0.0),
+            Anchor::Top => (0.5, 0.0),
+            Anchor::TopRight => (1.0, 0.0),
+            Anchor::Left => (0.0, 0.5),
+            Anchor::Center => (0.5, 0.5),
+            Anchor::Right => (1.0, 0.5),
+            Anchor::BottomLeft => (0.0, 1.0),
+            Anchor::Bottom => (0.5, 1.0),
+            Anchor::BottomRight => (1.0, 1.0),
+        }
+    }
+}
+
+/// How an image is mapped onto a canvas of different proportions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FitMode {
+    /// Fill the whole canvas, distorting the image if the proportions differ.
+    Stretch,
+    /// Fit the whole image inside the canvas, padding the rest with a colour.
+    Contain(Color),
+    /// Fill the whole canvas, cropping whatever overflows around the anchor.
+    Cover(Anchor),
+    /// Fit the whole image inside the canvas like [`FitMode::Contain`], then
+    /// shrink the canvas to the image instead of padding it.
+    Shrink,
+}
+
+/// Settings for turning an image into a canvas.
+#[derive(Clone, Debug)]
+pub struct LoadOptions {
+    /// Canvas width in cells.
+    pub width: usize,
+    /// Canvas height in cells.
+    pub height: usize,
+    /// How the image is resampled to the canvas.
+    pub filter: Filter,
+    /// The space pixels are averaged and blended in.
+    pub color_space: ColorSpace,
+    /// What to do when the image and canvas proportions differ.
+    pub fit: FitMode,
+    /// Height of a terminal cell divided by its width.
+    pub cell_aspect: f32,
+    /// Rotate and flip photos according to their EXIF orientation tag.
+    pub apply_orientation: bool,
+    /// How the pixels under each cell are drawn.
+    pub mode: CellMode,
+    /// Dithering of the colours the cells can show, see [`CellMode::quantise`].
+    pub dither: Dither,
+    /// Glyphs from darkest to brightest, see [`crate::Cell::from_color_with_ramp`].
+    pub ramp: Vec<char>,
+    /// Glyphs for [`CellMode::Shape`], the built-in ones when `None`.
+    pub glyphs: Option<GlyphSet>,
+    /// Colours the terminal can show, see [`ColorDepth::detect`].
+    pub color_depth: ColorDepth,
+    /// How colours are compared, see [`ColorMetric`].
+    pub color_metric: ColorMetric,
+    /// Colours every cell is limited to, dithered towards when dithering.
+    pub palette: Option<Palette>,
+}
+
+impl LoadOptions {
+    /// A `width` x `height` canvas with the default settings.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            filter: Filter::Box,
+            color_space: ColorSpace::Srgb,
+            fit: FitMode::Stretch,
+            cell_aspect: 2.0,
+            apply_orientation: true,
+            mode: CellMode::Ramp,
+            dither: Dither::None,
+            ramp: DEFAULT_RAMP.chars().collect(),
+            glyphs: None,
+            color_depth: ColorDepth::TrueColor,
+            color_metric: ColorMetric::Euclidean,
+            palette: None,
+        }
+    }
+}
+
+// Which part of the source image ends up where on the canvas.
+struct Placement {
+    crop: (usize, usize, usize, usize),
+    offset: (usize, usize),
+    size: (usize, usize),
+}
+
+impl Placement {
+    fn new(image_width: usize, image_height: usize, options: &LoadOptions) -> Self {
+        let (width, height) = (options.width, options.height);
+        let (iw, ih) = (image_width as f32, image_height as f32);
+        // Canvas extent measured in cell widths, so both axes share a unit.
+        let (cw, ch) = (width as f32, height as f32 * options.cell_aspect);
+        let full = (0, 0, image_width, image_height);
+
+        match options.fit {
+            FitMode::Stretch => Self {
+                crop: full,
+                offset: (0, 0),
+                size: (width, height),
+            },
+            FitMode::Contain(_) | FitMode::Shrink => {
+                let scale = (cw / iw).min(ch / ih);
+                let w = ((iw * scale).round() as usize).clamp(1, width);
+                let h = ((ih * scale / options.cell_aspect).round() as usize).clamp(1, height);
+                let offset = match options.fit {
+                    FitMode::Shrink => (0, 0),
+                    _ => ((width - w) / 2, (height - h) / 2),
+                };
+                Self {
+                    crop: full,
+                    offset,
+                    size: (w, h),
+                }
+            }
+            FitMode::Cover(anchor) => {
+                let scale = (cw / iw).max(ch / ih);
+                let w = ((cw / scale).round() as usize).clamp(1, image_width);
+                let h = ((ch / scale).round() as usize).clamp(1, image_height);
+                let (ax, ay) = anchor.offsets();
+                let x = ((image_width - w) as f32 * ax).round() as usize;
+                let y = ((image_height - h) as f32 * ay).round() as usize;
+                Self {
+                    crop: (x, y, w, h),
+                    offset: (0, 0),
+                    size: (width, height),
+                }
+            }
+        }
+    }
+}
+
+impl Canvas {
+    /// Decodes an image held in memory and converts it to a canvas.
+    pub fn from_bytes(bytes: &[u8], options: &LoadOptions) -> Result<Canvas, LoadError> {
+        image_to_canvas(decode_image(bytes, options)?, options)
+    }
+
+    /// Reads a whole image from `reader`, e.g. stdin, and converts it.
+    pub fn from_reader(mut reader: impl Read, options: &LoadOptions) -> Result<Canvas, LoadError> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+        Canvas::from_bytes(&bytes, options)
+    }
+}
+
+/// Decodes an image held in memory, keeping it around to be converted (and
+/// re-converted at other sizes) with [`image_to_canvas`].
+pub fn decode_image(bytes: &[u8], options: &LoadOptions) -> Result<Image, LoadError> {
+    let image = decode::decode(bytes)?;
+
+    if options.apply_orientation {
+        if let Some(orientation) = exif::orientation(bytes) {
+            return Ok(image.orient(orientation));
+        }
+    }
+
+    Ok(image)
+}
+
+/// Reads and converts the image at `path`.
+pub fn load_image_as_canvas(path: &str, options: &LoadOptions) -> Result<Canvas, LoadError> {
+    Canvas::from_bytes(&fs::read(path)?, options)
+}
+
+/// Reads an animated GIF and converts every frame, paired with its delay.
+pub fn load_gif_frames(path: &str, options: &LoadOptions) -> Result<Vec<(Canvas, Duration)>, LoadError> {
+    gif_frames_from_bytes(&fs::read(path)?, options)
+}
+
+/// Like [`load_gif_frames`] for a GIF held in memory.
+pub fn gif_frames_from_bytes(
+    bytes: &[u8],
+    options: &LoadOptions,
+) -> Result<Vec<(Canvas, Duration)>, LoadError> {
+    decode::decode_gif_frames(bytes)?
+        .into_iter()
+        .map(|(image, delay)| Ok((image_to_canvas(image, options)?, delay)))
+        .collect()
+}
+
+/// Fits, resamples and maps a decoded image onto a canvas.
+pub fn image_to_canvas(mut image: Image, options: &LoadOptions) -> Result<Canvas, LoadError> {
+    if options.width == 0 || options.height == 0 {
+        return Err(LoadError::ZeroSize);
+    }
+
+    let space = options.color_space;
+    let placement = Placement::new(image.width, image.height, options);
+    let (width, height) = match options.fit {
+        FitMode::Shrink => placement.size,
+        _ => (options.width, options.height),
+    };
+    let (x, y, w, h) = placement.crop;
+    if (w, h) != (image.width, image.height) {
+        image = image.crop(x, y, w, h);
+    }
+
+    // Each cell covers a block of sub-pixels, e.g. two stacked ones for half blocks.
+    let mode = options.mode;
+    let (w, h) = placement.size;
+    let (sx, sy) = mode.subpixels();
+    let mut colors = resample::resample(&image, w * sx, h * sy, options.filter, space)
+        .into_iter()
+        .map(|sample| resample::flatten(sample, Color::black(), space))
+        .collect::<Vec<_>>();
+    // Edges are found before dithering adds noise, across cell borders.
+    let gradients = match mode {
+        CellMode::Edge => cell::sobel(&colors, w * sx),
+        _ => vec![],
+    };
+    let metric = options.color_metric;
+    let step = options.palette.as_ref().map_or(mode.step(), Palette::step);
+    dither::dither(&mut colors, w * sx, &options.dither, step, |color| match &options.palette {
+        Some(palette) => palette.nearest(color, metric),
+        None => mode.quantise(color),
+    });
+    let snap = |cell| match &options.palette {
+        Some(palette) => palette.snap(cell, metric),
+        None => cell,
+    };
+
+    let letterbox = match options.fit {
+        FitMode::Contain(color) => color,
+        _ => Color::black(),
+    };
+    let letterbox = snap(mode.cell(&vec![letterbox; sx * sy], &options.ramp, 0.5, space));
+    let mut cells = vec![vec![letterbox; width]; height];
+    let (ox, oy) = placement.offset;
+    let mut block = Vec::with_capacity(sx * sy);
+    let mut block_gradients = Vec::with_capacity(sx * sy);
+
+    for y in 0..h {
+        for x in 0..w {
+            block.clear();
+            for row in colors.chunks(w * sx).skip(y * sy).take(sy) {
+                block.extend_from_slice(&row[x * sx..(x + 1) * sx]);
+            }
+            cells[oy + y][ox + x] = snap(match (mode, &options.glyphs) {
+                (CellMode::Shape, Some(glyphs)) => glyphs.cell(&block, space),
+                (CellMode::Edge, _) => {
+                    block_gradients.clear();
+                    for row in gradients.chunks(w * sx).skip(y * sy).take(sy) {
+                        block_gradients.extend_from_slice(&row[x * sx..(x + 1) * sx]);
+                    }
+                    let threshold = options.dither.threshold(x, y);
+                    Cell::edge(&block, &block_gradients, sx, &options.ramp, threshold, space)
+                }
+                _ => mode.cell(&block, &options.ramp, options.dither.threshold(x, y), space),
+            });
+        }
+    }
+
+    let mut canvas = Canvas::from_cells(cells, space);
+    canvas.color_depth = options.color_depth;
+    canvas.color_metric = options.color_metric;
+    Ok(canvas)
+}
+
+/// Plays decoded frames on a terminal-sized canvas like [`Canvas::play`],
+/// re-fitting them and repainting the whole screen whenever the window is
+/// resized. `reserved_rows` are kept free below the canvas.
+pub fn play_in_terminal(
+    frames: &[(Image, Duration)],
+    options: &LoadOptions,
+    reserved_rows: usize,
+    loops: Option<usize>,
+) -> Result<(), LoadError> {
+    let watcher = ResizeWatcher::new()?;
+    let mut options = options.clone();
+    let fit = |options: &mut LoadOptions| -> Result<Vec<(Canvas, Duration)>, LoadError> {
+        if let Some((width, height)) = term::canvas_size(reserved_rows) {
+            options.width = width;
+            options.height = height;
+        }
+        frames
+            .iter()
+            .map(|(image, delay)| Ok((image_to_canvas(image.clone(), options)?, *delay)))
+            .collect()
+    };
+
+    let mut canvases = fit(&mut options)?;
+    let Some((first, _)) = canvases.first() else {
+        return Ok(());
+    };
+    let mut screen = first.clone();
+    let mut played = 0;
+
+    while loops.is_none_or(|loops| played < loops) {
+        for i in 0..canvases.len() {
+            if watcher.resized() {
+                canvases = fit(&mut options)?;
+                screen.invalidate();
+            }
+            let start = Instant::now();
+            screen.cells = canvases[i].0.cells.clone();
+            screen.display();
+            thread::sleep(canvases[i].1.saturating_sub(start.elapsed()));
+        }
+        played += 1;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn place(image: (usize, usize), canvas: (usize, usize), fit: FitMode, cell_aspect: f32) -> Placement {
+        let mut options = LoadOptions::new(canvas.0, canvas.1);
+        options.fit = fit;
+        options.cell_aspect = cell_aspect;
+        Placement::new(image.0, image.1, &options)
+    }
+
+    #[test]
+    fn stretch_fills_the_canvas() {
+        let p = place((200, 100), (30, 30), FitMode::Stretch, 2.0);
+        assert_eq!((p.crop, p.offset, p.size), ((0, 0, 200, 100), (0, 0), (30, 30)));
+    }
+
+    #[test]
+    fn contain_letterboxes_and_shrink_does_not() {
+        let black = FitMode::Contain(Color::black());
+        // 40 cells across are 40 wide; 20 cells down are 40 wide at 1:2.
+        let p = place((200, 100), (40, 20), black, 2.0);
+        assert_eq!((p.crop, p.offset, p.size), ((0, 0, 200, 100), (0, 5), (40, 10)));
+        let p = place((100, 200), (40, 20), black, 2.0);
+        assert_eq!((p.offset, p.size), ((10, 0), (20, 20)));
+
+        let p = place((200, 100), (40, 20), FitMode::Shrink, 2.0);
+        assert_eq!((p.offset, p.size), ((0, 0), (40, 10)));
+    }
+
+    #[test]
+    fn cell_aspect_scales_rows() {
+        let black = FitMode::Contain(Color::black());
+        let p = place((200, 100), (40, 20), black, 1.0);
+        assert_eq!((p.offset, p.size), ((0, 0), (40, 20)));
+        let p = place((200, 100), (40, 40), black, 4.0);
+        assert_eq!((p.offset, p.size), ((0, 17), (40, 5)));
+    }
+
+    #[test]
+    fn cover_crops_around_the_anchor() {
+        // A 20x20 canvas is square at 1:2 cells, so a 50x100 strip is kept.
+        let crop = |anchor| place((200, 100), (20, 20), FitMode::Cover(anchor), 2.0).crop;
+        assert_eq!(crop(Anchor::TopLeft), (0, 0, 50, 100));
+        assert_eq!(crop(Anchor::Center), (75, 0, 50, 100));
+        assert_eq!(crop(Anchor::BottomRight), (150, 0, 50, 100));
+
+        let p = place((100, 200), (20, 5), FitMode::Cover(Anchor::Bottom), 2.0);
+        assert_eq!((p.crop, p.offset, p.size), ((0, 150, 100, 50), (0, 0), (20, 5)));
+    }
+}
@@ -0,0 +1,212 @@
+//! Writing canvases out as ANSI text, plain text or HTML.
+
+use std::{
+    env,
+    io::{self, Write},
+};
+
+use crate::{Canvas, Cell, Color, ColorMetric};
+
+/// How [`Canvas::write_to`] encodes a canvas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// Escape sequences for a terminal (or `cat`-ing the file into one later).
+    Ansi,
+    /// Just the glyphs.
+    Text,
+    /// A self-contained `<pre>` block with inline colours.
+    Html,
+}
+
+/// Which colours [`Canvas::write_to`] emits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorMode {
+    /// Foreground and background colours.
+    Full,
+    /// Foreground colours over the terminal's own background.
+    Foreground,
+    /// No colours at all.
+    None,
+}
+
+/// How many colours the terminal can show, and so which escape sequences
+/// cells are written with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorDepth {
+    /// 24-bit `38;2` sequences.
+    TrueColor,
+    /// The xterm 256-colour palette, e.g. tmux without truecolor enabled.
+    Ansi256,
+    /// The 16 standard colours, e.g. the Linux console.
+    Ansi16,
+}
+
+impl ColorDepth {
+    /// Guesses the depth from `COLORTERM` and `TERM`, assuming truecolor when
+    /// neither says otherwise.
+    pub fn detect() -> Self {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        Self::from_env(&colorterm, env::var("TERM").ok().as_deref())
+    }
+
+    fn from_env(colorterm: &str, term: Option<&str>) -> Self {
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            return ColorDepth::TrueColor;
+        }
+        match term {
+            Some(term) if term.ends_with("-direct") => ColorDepth::TrueColor,
+            Some(term) if term.contains("256color") => ColorDepth::Ansi256,
+            Some(term) if !term.is_empty() => ColorDepth::Ansi16,
+            _ => ColorDepth::TrueColor,
+        }
+    }
+
+    /// The escape sequence setting the foreground to `color`, matched to the
+    /// nearest palette colour by `metric`.
+    pub fn fg(&self, color: Color, metric: ColorMetric) -> String {
+        match self {
+            ColorDepth::TrueColor => format!("\x1b[38;2;{};{};{}m", color.red, color.green, color.blue),
+            ColorDepth::Ansi256 => format!("\x1b[38;5;{}m", color.to_ansi256(metric)),
+            ColorDepth::Ansi16 => match color.to_ansi16(metric) {
+                i @ 0..8 => format!("\x1b[{}m", 30 + i),
+                i => format!("\x1b[{}m", 90 + i - 8),
+            },
+        }
+    }
+
+    /// The escape sequence setting the background to `color`.
+    pub fn bg(&self, color: Color, metric: ColorMetric) -> String {
+        match self {
+            ColorDepth::TrueColor => format!("\x1b[48;2;{};{};{}m", color.red, color.green, color.blue),
+            ColorDepth::Ansi256 => format!("\x1b[48;5;{}m", color.to_ansi256(metric)),
+            ColorDepth::Ansi16 => match color.to_ansi16(metric) {
+                i @ 0..8 => format!("\x1b[{}m", 40 + i),
+                i => format!("\x1b[{}m", 100 + i - 8),
+            },
+        }
+    }
+}
+
+impl Canvas {
+    /// Writes a static snapshot of the canvas, one line per row.
+    pub fn write_to(&self, out: &mut impl Write, format: OutputFormat, mode: ColorMode) -> io::Result<()> {
+        match format {
+            OutputFormat::Ansi => self.write_ansi(out, mode),
+            OutputFormat::Text => self.write_ansi(out, ColorMode::None),
+            OutputFormat::Html => self.write_html(out, mode),
+        }
+    }
+
+    fn write_ansi(&self, out: &mut impl Write, mode: ColorMode) -> io::Result<()> {
+        let (depth, metric) = (self.color_depth, self.color_metric);
+        for row in self.cells.iter() {
+            // Compared as escape codes, so colours the depth shows the same
+            // don't repeat them.
+            let mut last: Option<String> = None;
+            for cell in row {
+                let escape = match mode {
+                    ColorMode::Full => format!("{}{}", depth.fg(cell.fg, metric), depth.bg(cell.bg, metric)),
+                    ColorMode::Foreground => depth.fg(cell.fg, metric),
+                    ColorMode::None => String::new(),
+                };
+                if last.as_ref() != Some(&escape) {
+                    write!(out, "{}", escape)?;
+                }
+                write!(out, "{}", cell.char)?;
+                last = Some(escape);
+            }
+            if mode == ColorMode::None {
+                writeln!(out)?;
+            } else {
+                writeln!(out, "\x1b[0m")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_html(&self, out: &mut impl Write, mode: ColorMode) -> io::Result<()> {
+        let hex = |c: Color| format!("#{:02x}{:02x}{:02x}", c.red, c.green, c.blue);
+        let style = |cell: &Cell| match mode {
+            ColorMode::Full => format!("color:{};background:{}", hex(cell.fg), hex(cell.bg)),
+            ColorMode::Foreground => format!("color:{}", hex(cell.fg)),
+            ColorMode::None => String::new(),
+        };
+
+        writeln!(out, "<pre style=\"font-family:monospace;line-height:1\">")?;
+        for row in self.cells.iter() {
+            let mut i = 0;
+            while i < row.len() {
+                // Merge runs of identically styled cells into one span.
+                let current = style(&row[i]);
+                let mut text = String::new();
+                while i < row.len() && style(&row[i]) == current {
+                    match row[i].char {
+                        '<' => text.push_str("&lt;"),
+                        '>' => text.push_str("&gt;"),
+                        '&' => text.push_str("&amp;"),
+                        c => text.push(c),
+                    }
+                    i += 1;
+                }
+                if current.is_empty() {
+                    write!(out, "{}", text)?;
+                } else {
+                    write!(out, "<span style=\"{}\">{}</span>", current, text)?;
+                }
+            }
+            writeln!(out)?;
+        }
+        writeln!(out, "</pre>")
+    }
+}
+
+/// Replays previously saved output (e.g. a `.out` file written with
+/// [`OutputFormat::Ansi`]) on a terminal in raw mode, where a bare `\n` no
+/// longer returns the cursor to the first column.
+pub fn write_raw(text: &str, out: &mut impl Write) -> io::Result<()> {
+    for line in text.split_inclusive('\n') {
+        match line.strip_suffix('\n') {
+            Some(line) => write!(out, "{}\r\n", line)?,
+            None => write!(out, "{}", line)?,
+        }
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ColorSpace;
+
+    #[test]
+    fn depth_from_the_environment() {
+        let detect = ColorDepth::from_env;
+        assert_eq!(detect("truecolor", Some("screen-256color")), ColorDepth::TrueColor);
+        assert_eq!(detect("24bit", Some("linux")), ColorDepth::TrueColor);
+        assert_eq!(detect("", Some("xterm-direct")), ColorDepth::TrueColor);
+        assert_eq!(detect("", Some("screen-256color")), ColorDepth::Ansi256);
+        assert_eq!(detect("", Some("linux")), ColorDepth::Ansi16);
+        // Nothing lower than 16 colours is told apart.
+        assert_eq!(detect("", Some("dumb")), ColorDepth::Ansi16);
+        assert_eq!(detect("", Some("")), ColorDepth::TrueColor);
+        assert_eq!(detect("", None), ColorDepth::TrueColor);
+    }
+
+    #[test]
+    fn escapes_are_not_repeated_for_the_same_code() {
+        // Two reds that share a 256-colour code but not a truecolor one.
+        let cells = vec![vec![Cell::new('a', Color::new(200, 0, 0), Color::black()), Cell::new('b', Color::new(201, 0, 0), Color::black())]];
+        let mut canvas = Canvas::from_cells(cells, ColorSpace::Srgb);
+        let escapes = |canvas: &Canvas, fg: &str| {
+            let mut out = vec![];
+            canvas.write_to(&mut out, OutputFormat::Ansi, ColorMode::Full).unwrap();
+            String::from_utf8(out).unwrap().matches(fg).count()
+        };
+
+        assert_eq!(escapes(&canvas, "\x1b[38;2;"), 2);
+        canvas.color_depth = ColorDepth::Ansi256;
+        assert_eq!(escapes(&canvas, "\x1b[38;5;"), 1);
+        canvas.color_depth = ColorDepth::Ansi16;
+        assert_eq!(escapes(&canvas, "\x1b[31m"), 1);
+    }
+}
@@ -0,0 +1,357 @@
+//! Fixed sets of colours to render in, loaded from palette files, built in, or
+//! picked from an image.
+
+use std::{fmt, fs, io};
+
+use crate::{Cell, Color, ColorMetric, Image};
+
+// At most this many pixels are sampled when picking a palette from an image.
+const MAX_SAMPLES: usize = 1 << 16;
+const KMEANS_ITERATIONS: usize = 8;
+
+const PICO8: &[u32] = &[
+    0x000000, 0x1D2B53, 0x7E2553, 0x008751, 0xAB5236, 0x5F574F, 0xC2C3C7, 0xFFF1E8, 0xFF004D,
+    0xFFA300, 0xFFEC27, 0x00E436, 0x29ADFF, 0x83769C, 0xFF77A8, 0xFFCCAA,
+];
+const GAME_BOY: &[u32] = &[0x0F380F, 0x306230, 0x8BAC0F, 0x9BBC0F];
+const CGA: &[u32] = &[
+    0x000000, 0x0000AA, 0x00AA00, 0x00AAAA, 0xAA0000, 0xAA00AA, 0xAA5500, 0xAAAAAA, 0x555555,
+    0x5555FF, 0x55FF55, 0x55FFFF, 0xFF5555, 0xFF55FF, 0xFFFF55, 0xFFFFFF,
+];
+
+/// Why a palette could not be loaded.
+#[derive(Debug)]
+pub enum PaletteError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// A line or colour did not parse.
+    Malformed(String),
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaletteError::Io(err) => write!(f, "could not read palette: {}", err),
+            PaletteError::Malformed(message) => write!(f, "could not parse palette: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PaletteError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PaletteError {
+    fn from(err: io::Error) -> Self {
+        PaletteError::Io(err)
+    }
+}
+
+/// A fixed set of colours that every cell is drawn in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    colors: Vec<Color>,
+}
+
+impl Palette {
+    /// Panics if `colors` is empty, as there would be nothing to map to.
+    pub fn new(colors: Vec<Color>) -> Self {
+        assert!(!colors.is_empty(), "a palette needs at least one colour");
+        Self { colors }
+    }
+
+    /// The colours, in the order they were given; never empty.
+    pub fn colors(&self) -> &[Color] {
+        &self.colors
+    }
+
+    /// One of the built-in palettes: `pico-8`, `game-boy` or `cga` (all 16
+    /// colours).
+    pub fn builtin(name: &str) -> Option<Self> {
+        let values = match name.to_ascii_lowercase().as_str() {
+            "pico-8" | "pico8" => PICO8,
+            "game-boy" | "gameboy" | "gb" => GAME_BOY,
+            "cga" => CGA,
+            _ => return None,
+        };
+        let colors = values
+            .iter()
+            .map(|v| Color::new((v >> 16) as u8, (v >> 8) as u8, *v as u8))
+            .collect();
+        Some(Self { colors })
+    }
+
+    /// Reads a GIMP (`.gpl`), Paint.NET (`.txt`) or plain hex list palette.
+    pub fn open(path: &str) -> Result<Self, PaletteError> {
+        let bytes = fs::read(path)?;
+        let text = std::str::from_utf8(&bytes)
+            .map_err(|_| PaletteError::Malformed("palette is not UTF-8".to_string()))?;
+        Self::parse(text)
+    }
+
+    /// Parses a palette file held in memory. GIMP palettes are told apart by
+    /// their header; anything else is read as one hex colour per line, either
+    /// `RRGGBB` (optionally after a `#`) or Paint.NET's `AARRGGBB`.
+    pub fn parse(text: &str) -> Result<Self, PaletteError> {
+        let colors = if text.trim_start().starts_with("GIMP Palette") {
+            parse_gpl(text)?
+        } else {
+            parse_hex(text)?
+        };
+        if colors.is_empty() {
+            return Err(PaletteError::Malformed("no colours found".to_string()));
+        }
+        Ok(Self { colors })
+    }
+
+    /// Picks a palette of up to `n` (at least one) colours for an image:
+    /// median cut splits the colours into `n` boxes, and k-means then moves
+    /// each box's colour to the centre of the pixels closest to it. Fully
+    /// transparent pixels are ignored.
+    pub fn from_image(image: &Image, n: usize) -> Self {
+        let stride = (image.pixels.len() / MAX_SAMPLES).max(1);
+        let samples = image
+            .pixels
+            .iter()
+            .step_by(stride)
+            .filter(|p| p[3] > 0)
+            .map(|&[r, g, b, _]| [r, g, b])
+            .collect::<Vec<_>>();
+        if samples.is_empty() {
+            return Self::new(vec![Color::black()]);
+        }
+
+        let mut centres = median_cut(samples.clone(), n.max(1));
+        kmeans(&samples, &mut centres);
+        let mut colors = vec![];
+        for [r, g, b] in centres {
+            let color = Color::new(r.round() as u8, g.round() as u8, b.round() as u8);
+            if !colors.contains(&color) {
+                colors.push(color);
+            }
+        }
+        Self::new(colors)
+    }
+
+    /// The palette colour closest to `color` by `metric`.
+    pub fn nearest(&self, color: Color, metric: ColorMetric) -> Color {
+        *self
+            .colors
+            .iter()
+            .min_by(|a, b| metric.distance(color, **a).total_cmp(&metric.distance(color, **b)))
+            .unwrap()
+    }
+
+    /// `cell` with both its colours replaced by their nearest palette colours.
+    pub fn snap(&self, cell: Cell, metric: ColorMetric) -> Cell {
+        Cell::new(cell.char, self.nearest(cell.fg, metric), self.nearest(cell.bg, metric))
+    }
+
+    /// The typical distance between neighbouring palette colours, for
+    /// ordered dithering: the mean distance from each colour to its closest
+    /// other one, in RGB.
+    pub fn step(&self) -> f32 {
+        if self.colors.len() < 2 {
+            return 0.0;
+        }
+        let total = self
+            .colors
+            .iter()
+            .enumerate()
+            .map(|(i, a)| {
+                self.colors
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i)
+                    .map(|(_, b)| a.distance(*b))
+                    .fold(f64::MAX, f64::min)
+            })
+            .sum::<f64>();
+        (total / self.colors.len() as f64) as f32
+    }
+
+    /// The palette as a hex list, one `RRGGBB` per line, which [`Palette::parse`]
+    /// reads back.
+    pub fn to_hex(&self) -> String {
+        self.colors
+            .iter()
+            .map(|c| format!("{:02x}{:02x}{:02x}\n", c.red, c.green, c.blue))
+            .collect()
+    }
+}
+
+fn parse_gpl(text: &str) -> Result<Vec<Color>, PaletteError> {
+    let mut colors = vec![];
+    for line in text.lines().skip(1).map(str::trim) {
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("Name:")
+            || line.starts_with("Columns:")
+        {
+            continue;
+        }
+        // Three channels, then an optional name.
+        let channels = line
+            .split_whitespace()
+            .take(3)
+            .map(|v| v.parse::<u8>())
+            .collect::<Result<Vec<_>, _>>()
+            .ok()
+            .filter(|c| c.len() == 3)
+            .ok_or_else(|| PaletteError::Malformed(format!("bad GIMP palette line '{}'", line)))?;
+        colors.push(Color::new(channels[0], channels[1], channels[2]));
+    }
+    Ok(colors)
+}
+
+fn parse_hex(text: &str) -> Result<Vec<Color>, PaletteError> {
+    let mut colors = vec![];
+    for line in text.lines().map(str::trim) {
+        // Paint.NET comments start with `;`, and `#` starts a comment unless
+        // a colour follows.
+        let hex = line.strip_prefix('#').unwrap_or(line);
+        let commented = line.starts_with('#')
+            && (hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()));
+        if line.is_empty() || line.starts_with(';') || commented {
+            continue;
+        }
+        // `from_str_radix` alone would take a leading `+`.
+        let value = Some(hex)
+            .filter(|hex| (hex.len() == 6 || hex.len() == 8) && hex.chars().all(|c| c.is_ascii_hexdigit()))
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| PaletteError::Malformed(format!("bad hex colour '{}'", line)))?;
+        // The alpha byte of `AARRGGBB` falls off the top.
+        colors.push(Color::new((value >> 16) as u8, (value >> 8) as u8, value as u8));
+    }
+    Ok(colors)
+}
+
+// Splits the colours into up to `n` boxes, each time halving the box with the
+// widest channel at that channel's median, and returns the boxes' means.
+fn median_cut(samples: Vec<[u8; 3]>, n: usize) -> Vec<[f32; 3]> {
+    let range = |pixels: &[[u8; 3]]| {
+        (0..3)
+            .map(|k| {
+                let (min, max) = pixels
+                    .iter()
+                    .fold((u8::MAX, u8::MIN), |(min, max), p| (min.min(p[k]), max.max(p[k])));
+                (max - min, k)
+            })
+            .max()
+            .unwrap()
+    };
+
+    let mut boxes = vec![samples];
+    while boxes.len() < n {
+        let Some((index, (_, channel))) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| (i, range(b)))
+            .filter(|(_, (spread, _))| *spread > 0)
+            .max_by_key(|(_, r)| *r)
+        else {
+            break;
+        };
+        let mut pixels = boxes.swap_remove(index);
+        pixels.sort_unstable_by_key(|p| p[channel]);
+        let upper = pixels.split_off(pixels.len() / 2);
+        boxes.push(pixels);
+        boxes.push(upper);
+    }
+
+    boxes
+        .iter()
+        .map(|pixels| {
+            let mut sum = [0.0f32; 3];
+            for p in pixels {
+                for k in 0..3 {
+                    sum[k] += p[k] as f32;
+                }
+            }
+            sum.map(|s| s / pixels.len() as f32)
+        })
+        .collect()
+}
+
+// Lloyd's algorithm in RGB: moves every centre to the mean of the samples
+// nearest it. Centres that attract no samples stay put.
+fn kmeans(samples: &[[u8; 3]], centres: &mut [[f32; 3]]) {
+    for _ in 0..KMEANS_ITERATIONS {
+        let mut sums = vec![[0.0f32; 3]; centres.len()];
+        let mut counts = vec![0usize; centres.len()];
+        for sample in samples {
+            let p = sample.map(|v| v as f32);
+            let squared = |c: &[f32; 3]| (0..3).map(|k| (p[k] - c[k]).powi(2)).sum::<f32>();
+            let nearest = (0..centres.len())
+                .min_by(|&a, &b| squared(&centres[a]).total_cmp(&squared(&centres[b])))
+                .unwrap();
+            for k in 0..3 {
+                sums[nearest][k] += p[k];
+            }
+            counts[nearest] += 1;
+        }
+
+        let mut moved = false;
+        for ((centre, sum), count) in centres.iter_mut().zip(sums).zip(counts) {
+            if count > 0 {
+                let mean = sum.map(|s| s / count as f32);
+                moved |= mean != *centre;
+                *centre = mean;
+            }
+        }
+        if !moved {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gimp_palette() {
+        let gpl = "GIMP Palette
+Name: Test
+Columns: 2
+# A comment
+  0   0   0\tBlack
+255 128  10 Orange red
+";
+        let palette = Palette::parse(gpl).unwrap();
+        assert_eq!(palette.colors(), [Color::new(0, 0, 0), Color::new(255, 128, 10)]);
+        assert!(Palette::parse("GIMP Palette\n1 2\n").is_err());
+    }
+
+    #[test]
+    fn paint_net_palette() {
+        // Alpha comes first and is dropped.
+        let txt = "; paint.net Palette File\n; Colors: 2\nFF112233\n80aabbcc\n";
+        let palette = Palette::parse(txt).unwrap();
+        assert_eq!(palette.colors(), [Color::new(0x11, 0x22, 0x33), Color::new(0xAA, 0xBB, 0xCC)]);
+    }
+
+    #[test]
+    fn hex_list() {
+        let palette = Palette::parse("# colours\n#ff0000\n00ff00\n\n").unwrap();
+        assert_eq!(palette.colors(), [Color::new(255, 0, 0), Color::new(0, 255, 0)]);
+        assert_eq!(Palette::parse(&palette.to_hex()).unwrap(), palette);
+        assert!(Palette::parse("12345\n").is_err());
+        assert!(Palette::parse("+12345\n").is_err());
+        assert!(Palette::parse("+1234567\n").is_err());
+        assert!(Palette::parse("; nothing here\n").is_err());
+    }
+
+    #[test]
+    fn nearest_colour() {
+        let palette = Palette::builtin("game-boy").unwrap();
+        let nearest = palette.nearest(Color::new(0, 0, 0), ColorMetric::Euclidean);
+        assert_eq!(nearest, Color::new(0x0F, 0x38, 0x0F));
+    }
+}
@@ -0,0 +1,216 @@
+//! Scaling decoded images down (or up) to the canvas resolution.
+
+use std::f32::consts::PI;
+
+use palette::LinSrgb;
+
+use crate::{decode::Image, Color, ColorSpace};
+
+/// The reconstruction filter used when scaling images.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    /// Area average: every source pixel contributes by how much of it the cell covers.
+    Box,
+    /// Linear interpolation between the nearest pixels, widened when shrinking.
+    Bilinear,
+    /// Windowed sinc over three lobes: sharpest, at the cost of slight ringing.
+    Lanczos3,
+}
+
+impl Filter {
+    fn support(&self) -> f32 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Bilinear => 1.0,
+            Filter::Lanczos3 => 3.0,
+        }
+    }
+
+    fn kernel(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            Filter::Box => (x < 0.5) as u8 as f32,
+            Filter::Bilinear => (1.0 - x).max(0.0),
+            Filter::Lanczos3 => {
+                if x < 1e-5 {
+                    1.0
+                } else if x < 3.0 {
+                    let px = PI * x;
+                    3.0 * px.sin() * (px / 3.0).sin() / (px * px)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    // For every destination index, the first source index and the normalised
+    // weights of the source samples starting there.
+    fn weights(&self, src: usize, dst: usize) -> Vec<(usize, Vec<f32>)> {
+        let scale = src as f32 / dst as f32;
+
+        (0..dst)
+            .map(|i| {
+                let (start, mut weights) = if *self == Filter::Box {
+                    let lo = i as f32 * scale;
+                    let hi = (lo + scale).min(src as f32);
+                    let start = lo.floor() as usize;
+                    let end = (hi.ceil() as usize).clamp(start + 1, src);
+                    let weights = (start..end)
+                        .map(|j| (hi.min(j as f32 + 1.0) - lo.max(j as f32)).max(0.0))
+                        .collect::<Vec<_>>();
+                    (start, weights)
+                } else {
+                    let center = (i as f32 + 0.5) * scale;
+                    let stretch = scale.max(1.0);
+                    let support = self.support() * stretch;
+                    let start = (center - support).floor().max(0.0) as usize;
+                    let end = ((center + support).ceil() as usize).min(src).max(start + 1);
+                    let weights = (start..end)
+                        .map(|j| self.kernel((j as f32 + 0.5 - center) / stretch))
+                        .collect::<Vec<_>>();
+                    (start, weights)
+                };
+
+                let sum: f32 = weights.iter().sum();
+                if sum.abs() > f32::EPSILON {
+                    weights.iter_mut().for_each(|w| *w /= sum);
+                } else {
+                    weights = vec![1.0];
+                }
+                (start, weights)
+            })
+            .collect()
+    }
+}
+
+/// Resizes `image` to `width` x `height` samples using a separable filter.
+/// Returns premultiplied RGBA in 0.0..=255.0, row-major, with the colour
+/// channels encoded in `space`.
+pub fn resample(
+    image: &Image,
+    width: usize,
+    height: usize,
+    filter: Filter,
+    space: ColorSpace,
+) -> Vec<[f32; 4]> {
+    let premultiplied = image
+        .pixels
+        .iter()
+        .map(|[r, g, b, a]| {
+            let [r, g, b] = Color::new(*r, *g, *b).channels(space);
+            let alpha = *a as f32 / 255.0;
+            [r * alpha, g * alpha, b * alpha, *a as f32]
+        })
+        .collect::<Vec<_>>();
+
+    let columns = filter.weights(image.width, width);
+    let mut horizontal = vec![[0.0; 4]; width * image.height];
+
+    for y in 0..image.height {
+        let row = &premultiplied[y * image.width..(y + 1) * image.width];
+        for (x, (start, weights)) in columns.iter().enumerate() {
+            horizontal[y * width + x] = accumulate(weights, |k| row[start + k]);
+        }
+    }
+
+    let rows = filter.weights(image.height, height);
+    let mut result = vec![[0.0; 4]; width * height];
+
+    for (y, (start, weights)) in rows.iter().enumerate() {
+        for x in 0..width {
+            let mut sample = accumulate(weights, |k| horizontal[(start + k) * width + x]);
+            // Lanczos lobes can overshoot, so keep every channel within the alpha.
+            sample[3] = sample[3].clamp(0.0, 255.0);
+            for c in 0..3 {
+                sample[c] = sample[c].clamp(0.0, sample[3]);
+            }
+            result[y * width + x] = sample;
+        }
+    }
+
+    result
+}
+
+/// Composites a premultiplied sample from `resample` over `background`.
+pub fn flatten(sample: [f32; 4], background: Color, space: ColorSpace) -> Color {
+    let [r, g, b, a] = sample;
+    let [br, bg, bb] = background.channels(space);
+    let coverage = 1.0 - a / 255.0;
+    let (r, g, b) = (r + br * coverage, g + bg * coverage, b + bb * coverage);
+
+    match space {
+        ColorSpace::Srgb => Color::new(r as u8, g as u8, b as u8),
+        ColorSpace::Linear => Color::from_linear(LinSrgb::new(r / 255.0, g / 255.0, b / 255.0)),
+    }
+}
+
+fn accumulate(weights: &[f32], sample: impl Fn(usize) -> [f32; 4]) -> [f32; 4] {
+    let mut total = [0.0; 4];
+    for (k, weight) in weights.iter().enumerate() {
+        let s = sample(k);
+        for c in 0..4 {
+            total[c] += s[c] * weight;
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(values: &[u8]) -> Image {
+        let mut image = Image::new(values.len(), 1);
+        for (x, &v) in values.iter().enumerate() {
+            image.set(x, 0, [v, v, v, 255]);
+        }
+        image
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn box_weights_cover_partial_pixels() {
+        // Each of the two samples covers one and a half source pixels.
+        let weights = Filter::Box.weights(3, 2);
+        assert_eq!(weights[0].0, 0);
+        assert!(close(weights[0].1[0], 2.0 / 3.0) && close(weights[0].1[1], 1.0 / 3.0));
+        assert_eq!(weights[1].0, 1);
+        assert!(close(weights[1].1[0], 1.0 / 3.0) && close(weights[1].1[1], 2.0 / 3.0));
+
+        for (src, dst) in [(3, 2), (7, 3), (10, 4), (5, 5), (2, 7)] {
+            for (_, w) in Filter::Box.weights(src, dst) {
+                assert!(close(w.iter().sum(), 1.0), "{} -> {}: {:?}", src, dst, w);
+            }
+        }
+    }
+
+    #[test]
+    fn bilinear_upscale_interpolates() {
+        let samples = resample(&row(&[0, 255]), 4, 1, Filter::Bilinear, ColorSpace::Srgb);
+        let red: Vec<f32> = samples.iter().map(|s| s[0]).collect();
+        for (got, want) in red.iter().zip([0.0, 63.75, 191.25, 255.0]) {
+            assert!(close(*got, want), "{:?}", red);
+        }
+    }
+
+    #[test]
+    fn lanczos_overshoot_is_clamped() {
+        let image = row(&[0, 0, 0, 0, 255, 255, 255, 255]);
+        // Unclamped, the lobes ring below 0 and above 255 around the step.
+        let raw: Vec<f32> = Filter::Lanczos3
+            .weights(8, 16)
+            .iter()
+            .map(|(start, w)| w.iter().enumerate().map(|(k, w)| image.get(start + k, 0)[0] as f32 * w).sum())
+            .collect();
+        assert!(raw.iter().any(|&v| v < 0.0) && raw.iter().any(|&v| v > 255.0));
+
+        for sample in resample(&image, 16, 1, Filter::Lanczos3, ColorSpace::Srgb) {
+            assert!(sample[0] >= 0.0 && sample[0] <= 255.0, "{:?}", sample);
+            assert!(sample[0] <= sample[3]);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Shapes and widgets that can be drawn over a canvas.
+
+use crate::Color;
+
+/// A point or size in cell coordinates.
+#[derive(Clone, Copy)]
+pub struct Vector2 {
+    /// Column, or width.
+    pub x: f32,
+    /// Row, or height.
+    pub y: f32,
+}
+
+impl Vector2 {
+    /// Creates a vector from its components.
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    /// Euclidean distance between the two points.
+    pub fn distance(self, other: Vector2) -> f32 {
+        ((self.x - other.x).powf(2.0)
+        + (self.y - other.y).powf(2.0)).sqrt()
+    }
+}
+
+/// An axis-aligned rectangle in cell coordinates.
+#[derive(Clone)]
+pub struct Rectangle {
+    /// The top-left corner.
+    pub position: Vector2,
+    /// Width and height.
+    pub size: Vector2,
+}
+
+impl Rectangle {
+    /// Creates a rectangle from its top-left corner and size.
+    pub fn new(position: Vector2, size: Vector2) -> Self {
+        Self { position, size }
+    }
+
+    /// Builds a rectangle from integer coordinates.
+    pub fn raw(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self::new(
+            Vector2::new(x as f32, y as f32),
+            Vector2::new(width as f32, height as f32),
+        )
+    }
+}
+
+/// A circle whose horizontal radius is stretched for the 1:2 cell aspect.
+#[derive(Clone)]
+pub struct Circle {
+    /// Column of the centre.
+    pub x: f32,
+    /// Row of the centre.
+    pub y: f32,
+    /// Radius in rows; twice as many columns across.
+    pub radius: f32,
+}
+
+impl Circle {
+    /// Creates a circle from its centre and radius.
+    pub fn new(x: f32, y: f32, radius: f32) -> Self {
+        Self { x, y, radius }
+    }
+}
+
+/// A framed, horizontally centred text box.
+#[derive(Clone)]
+pub struct DialogBox {
+    /// The text, with `\n` starting a new line.
+    pub text: String,
+    /// Width of the text area in columns, not counting padding or frame.
+    pub width: f32,
+    /// Height of the text area in rows, not counting padding or frame.
+    pub height: f32,
+    /// Row index of the top of the box; the frame sits on the row above.
+    pub position: f32,
+    /// Columns of padding left of the text.
+    pub x_pad: f32,
+    /// Rows of padding above the text.
+    pub y_pad: f32,
+    /// Foreground colour of the text.
+    pub text_color: Color,
+}
+
+impl DialogBox {
+    /// A box with one cell of padding and white text.
+    pub fn new(text: &str, width: f32, height: f32, position: f32) -> Self {
+        Self {
+            text: text.to_string(),
+            width,
+            height,
+            position,
+            x_pad: 1.0,
+            y_pad: 1.0,
+            text_color: Color::white(), 
+        }
+    }
+}
+
@@ -0,0 +1,114 @@
+//! Querying and controlling the terminal the canvas is drawn on.
+
+use std::{
+    io::{self, stdout, Write},
+    panic, process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Once,
+    },
+    thread,
+};
+
+use signal_hook::{
+    consts::{SIGINT, SIGTERM, SIGWINCH},
+    iterator::{backend::Handle, Signals},
+    SigId,
+};
+
+/// The terminal window's size in cells as `(columns, rows)`, or `None` when
+/// stdout is not a terminal.
+pub fn size() -> Option<(usize, usize)> {
+    let (columns, rows) = termion::terminal_size().ok()?;
+    (columns > 0 && rows > 0).then_some((columns as usize, rows as usize))
+}
+
+/// The largest canvas that fits in the terminal while keeping `reserved_rows`
+/// free below it, e.g. for a status line or the shell prompt.
+pub fn canvas_size(reserved_rows: usize) -> Option<(usize, usize)> {
+    let (columns, rows) = size()?;
+    Some((columns, rows.saturating_sub(reserved_rows).max(1)))
+}
+
+/// Notices when the terminal window is resized (`SIGWINCH`).
+pub struct ResizeWatcher {
+    resized: Arc<AtomicBool>,
+    id: SigId,
+}
+
+impl ResizeWatcher {
+    /// Starts listening for `SIGWINCH`.
+    pub fn new() -> io::Result<Self> {
+        let resized = Arc::new(AtomicBool::new(false));
+        let id = signal_hook::flag::register(SIGWINCH, Arc::clone(&resized))?;
+        Ok(Self { resized, id })
+    }
+
+    /// Whether the window was resized since the last call.
+    pub fn resized(&self) -> bool {
+        self.resized.swap(false, Ordering::Relaxed)
+    }
+}
+
+impl Drop for ResizeWatcher {
+    fn drop(&mut self) {
+        signal_hook::low_level::unregister(self.id);
+    }
+}
+
+// Whether a guard currently owns the screen, shared with the panic hook.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static PANIC_HOOK: Once = Once::new();
+
+/// Takes over the terminal for full-screen drawing: switches to the alternate
+/// screen and hides the cursor, then puts everything back when dropped, on a
+/// panic, or when the process is interrupted with `SIGINT`/`SIGTERM`.
+pub struct TerminalGuard {
+    signals: Handle,
+}
+
+impl TerminalGuard {
+    /// Takes over the screen and installs the hooks that give it back.
+    pub fn new() -> io::Result<Self> {
+        PANIC_HOOK.call_once(|| {
+            let previous = panic::take_hook();
+            // Restore first so the panic message lands on the normal screen.
+            panic::set_hook(Box::new(move |info| {
+                restore();
+                previous(info);
+            }));
+        });
+
+        let mut signals = Signals::new([SIGINT, SIGTERM])?;
+        let handle = signals.handle();
+        thread::spawn(move || {
+            if let Some(signal) = signals.forever().next() {
+                restore();
+                process::exit(128 + signal);
+            }
+        });
+
+        let mut out = stdout();
+        write!(out, "\x1b[?1049h\x1b[?25l\x1b[2J")?;
+        out.flush()?;
+        ACTIVE.store(true, Ordering::SeqCst);
+
+        Ok(Self { signals: handle })
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        self.signals.close();
+        restore();
+    }
+}
+
+// Resets colours, shows the cursor and leaves the alternate screen, once.
+fn restore() {
+    if ACTIVE.swap(false, Ordering::SeqCst) {
+        let mut out = stdout();
+        let _ = write!(out, "\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = out.flush();
+    }
+}